@@ -3,6 +3,7 @@ use std::sync::LazyLock;
 pub static ENVIRONMENT: LazyLock<Environment> =
   LazyLock::new(Environment::from_environment);
 
+#[allow(clippy::struct_excessive_bools)]
 pub struct Environment {
   pub root:                       String,
   pub css_external:               Option<String>,
@@ -17,11 +18,45 @@ pub struct Environment {
   pub proxy_by_default:           bool,
   pub keep_gemini:                Option<Vec<String>>,
   pub embed_images:               Option<String>,
+  pub embed_media:                Option<String>,
   pub http09:                     bool,
   pub http09_port:                u16,
+  pub http09_text:                bool,
+  pub cache_ttl:                  u64,
+  pub cache_capacity:             usize,
+  pub cache_directory:            Option<String>,
+  pub cache_directory_capacity:   u64,
+  pub cache_purge_token:          Option<String>,
+  pub max_redirects:              usize,
+  pub status_codes:               Vec<(String, u16)>,
+  pub http_redirects:             bool,
+  pub client_certificates:        Vec<(String, String, String)>,
+  pub ephemeral_identities:       bool,
+  pub ephemeral_lifetime:         u64,
+  pub ephemeral_capacity:         usize,
+  pub ephemeral_session_capacity: usize,
+  pub tofu_store:                 Option<String>,
+  pub tofu_refuse:                bool,
+  pub tofu_pins:                  Vec<(String, String)>,
+  pub inline_markup:              Option<String>,
+  pub inline_markup_paths:        Vec<(String, String)>,
+  pub heading_links:              bool,
+  pub table_of_contents:          Vec<String>,
+  pub table_of_contents_minimum:  usize,
+  pub syntax_highlighting:        bool,
+  pub ansi_escapes:               Option<String>,
+  pub preformatted_alt_text:      Option<String>,
+  pub ascii_art_detection:        bool,
+  pub stream_responses:           bool,
+  pub max_response_size:          usize,
+  pub json_cors_origins:          Vec<String>,
+  pub markdown_links:             Option<String>,
+  pub text_width:                 usize,
+  pub terminal_rendering:         bool,
 }
 
 impl Environment {
+  #[allow(clippy::too_many_lines)]
   fn from_environment() -> Self {
     Self {
       root:                       std::env::var("ROOT").unwrap_or_else(|_| {
@@ -35,8 +70,7 @@ impl Environment {
       primary_colour:             std::env::var("PRIMARY_COLOUR").ok(),
       favicon_external:           std::env::var("FAVICON_EXTERNAL").ok(),
       mathjax:                    std::env::var("MATHJAX")
-        .map(|v| v.to_lowercase() == "true")
-        .unwrap_or(true),
+        .map_or(true, |v| v.to_lowercase() == "true"),
       head:                       std::env::var("HEAD").ok(),
       header:                     std::env::var("HEADER").ok(),
       plain_text_route:           std::env::var("PLAIN_TEXT_ROUTE").ok(),
@@ -47,19 +81,151 @@ impl Environment {
         .map(|s| s.split(',').map(String::from).collect())
         .unwrap_or_default(),
       proxy_by_default:           std::env::var("PROXY_BY_DEFAULT")
-        .map(|v| v.to_lowercase() == "true")
-        .unwrap_or(true),
+        .map_or(true, |v| v.to_lowercase() == "true"),
       keep_gemini:                std::env::var("KEEP_GEMINI")
         .ok()
         .map(|s| s.split(',').map(String::from).collect()),
       embed_images:               std::env::var("EMBED_IMAGES").ok(),
+      embed_media:                std::env::var("EMBED_MEDIA").ok(),
       http09:                     std::env::var("HTTP09")
-        .map(|v| v.to_lowercase() == "true")
-        .unwrap_or(false),
+        .is_ok_and(|v| v.to_lowercase() == "true"),
       http09_port:                std::env::var("HTTP09_PORT")
         .ok()
         .and_then(|p| p.parse().ok())
         .unwrap_or(90),
+      http09_text:                std::env::var("HTTP09_TEXT")
+        .is_ok_and(|v| v.to_lowercase() == "true"),
+      cache_ttl:                  std::env::var("CACHE_TTL")
+        .ok()
+        .and_then(|t| t.parse().ok())
+        .unwrap_or(0),
+      cache_capacity:             std::env::var("CACHE_CAPACITY")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(64 * 1024 * 1024),
+      cache_directory:            std::env::var("CACHE_DIRECTORY").ok(),
+      cache_directory_capacity:   std::env::var("CACHE_DIRECTORY_CAPACITY")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(256 * 1024 * 1024),
+      cache_purge_token:          std::env::var("CACHE_PURGE_TOKEN").ok(),
+      max_redirects:              std::env::var("MAX_REDIRECTS")
+        .ok()
+        .and_then(|r| r.parse().ok())
+        .unwrap_or(5),
+      status_codes:               std::env::var("STATUS_CODES")
+        .map(|s| {
+          s.split(',')
+            .filter_map(|mapping| {
+              let (gemini, http) = mapping.split_once('=')?;
+
+              Some((gemini.trim().to_string(), http.trim().parse().ok()?))
+            })
+            .collect()
+        })
+        .unwrap_or_default(),
+      http_redirects:             std::env::var("HTTP_REDIRECTS")
+        .is_ok_and(|v| v.to_lowercase() == "true"),
+      client_certificates:        std::env::var("CLIENT_CERTIFICATES")
+        .map(|s| {
+          s.split(',')
+            .filter_map(|mapping| {
+              let (pattern, files) = mapping.split_once('=')?;
+              let (certificate, key) =
+                files.split_once(':').unwrap_or((files, files));
+
+              Some((
+                pattern.trim().to_string(),
+                certificate.trim().to_string(),
+                key.trim().to_string(),
+              ))
+            })
+            .collect()
+        })
+        .unwrap_or_default(),
+      ephemeral_identities:       std::env::var("EPHEMERAL_IDENTITIES")
+        .is_ok_and(|v| v.to_lowercase() == "true"),
+      ephemeral_lifetime:         std::env::var("EPHEMERAL_IDENTITY_LIFETIME")
+        .ok()
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(86400),
+      ephemeral_capacity:         std::env::var("EPHEMERAL_IDENTITY_CAPACITY")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(1024),
+      ephemeral_session_capacity: std::env::var("EPHEMERAL_SESSION_CAPACITY")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(16),
+      tofu_store:                 std::env::var("TOFU_STORE").ok(),
+      tofu_refuse:                std::env::var("TOFU_POLICY")
+        .is_ok_and(|v| v.to_lowercase() == "refuse"),
+      tofu_pins:                  std::env::var("TOFU_PINS")
+        .map(|s| {
+          s.split(',')
+            .filter(|pin| !pin.trim().is_empty())
+            .map(|pin| {
+              let (host, fingerprint) =
+                pin.split_once('=').unwrap_or((pin, ""));
+
+              (host.trim().to_string(), fingerprint.trim().to_string())
+            })
+            .collect()
+        })
+        .unwrap_or_default(),
+      inline_markup:              std::env::var("INLINE_MARKUP").ok(),
+      inline_markup_paths:        std::env::var("INLINE_MARKUP_PATHS")
+        .map(|s| {
+          s.split(',')
+            .filter_map(|mapping| {
+              let (pattern, markup) = mapping.rsplit_once('=')?;
+
+              Some((pattern.trim().to_string(), markup.trim().to_string()))
+            })
+            .collect()
+        })
+        .unwrap_or_default(),
+      heading_links:              std::env::var("HEADING_LINKS")
+        .is_ok_and(|v| v.to_lowercase() == "true"),
+      table_of_contents:          std::env::var("TABLE_OF_CONTENTS")
+        .map(|s| s.split(',').map(String::from).collect())
+        .unwrap_or_default(),
+      table_of_contents_minimum:  std::env::var("TABLE_OF_CONTENTS_MINIMUM")
+        .ok()
+        .and_then(|m| m.parse().ok())
+        .unwrap_or(3),
+      syntax_highlighting:        std::env::var("SYNTAX_HIGHLIGHTING")
+        .map_or(true, |v| v.to_lowercase() == "true"),
+      ansi_escapes:               std::env::var("ANSI_ESCAPES").ok(),
+      preformatted_alt_text:      std::env::var("PREFORMATTED_ALT_TEXT")
+        .ok()
+        .map(|v| v.to_lowercase()),
+      ascii_art_detection:        std::env::var("ASCII_ART_DETECTION")
+        .map_or(true, |v| v.to_lowercase() == "true"),
+      stream_responses:           std::env::var("STREAM_RESPONSES")
+        .map_or(true, |v| v.to_lowercase() == "true"),
+      max_response_size:          std::env::var("MAX_RESPONSE_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0),
+      json_cors_origins:          std::env::var("JSON_CORS_ORIGINS")
+        .map_or_else(
+          |_| vec!["*".to_string()],
+          |s| {
+            s.split(',')
+              .map(str::trim)
+              .filter(|origin| !origin.is_empty())
+              .map(String::from)
+              .collect()
+          },
+        ),
+      markdown_links:             std::env::var("MARKDOWN_LINKS").ok(),
+      text_width:                 std::env::var("TEXT_WIDTH")
+        .ok()
+        .and_then(|w| w.parse().ok())
+        .unwrap_or(72),
+      terminal_rendering:         std::env::var("TERMINAL_RENDERING")
+        .map_or(true, |v| v.to_lowercase() == "true"),
     }
   }
 }
@@ -0,0 +1,392 @@
+pub mod cache;
+pub mod certificate;
+pub mod tofu;
+
+use {
+  crate::environment::ENVIRONMENT,
+  bytes::Bytes,
+  germ::request::Status,
+  std::{pin::Pin, sync::Arc},
+  tokio::io::{AsyncReadExt, AsyncWriteExt},
+  url::Url,
+};
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Body =
+  Pin<Box<dyn futures_util::Stream<Item = std::io::Result<Bytes>> + Send>>;
+type Stream = tokio_rustls::client::TlsStream<tokio::net::TcpStream>;
+
+// A status, a space, at most 1024 bytes of meta, and a CRLF
+const MAXIMUM_HEADER_SIZE: usize = 1029;
+const CHUNK_SIZE: usize = 16 * 1024;
+
+#[derive(Clone)]
+pub struct Response {
+  status:      Status,
+  meta:        String,
+  content:     Option<Arc<[u8]>>,
+  certificate: Option<tofu::Certificate>,
+}
+
+impl Response {
+  pub fn new(status: Status, meta: String, content: Option<Vec<u8>>) -> Self {
+    Self { status, meta, content: content.map(Arc::from), certificate: None }
+  }
+
+  pub const fn status(&self) -> &Status { &self.status }
+
+  pub fn meta(&self) -> &str { &self.meta }
+
+  pub fn content(&self) -> Option<String> {
+    self.decode().map(|(content, _)| content)
+  }
+
+  // Decodes the body according to its `charset` parameter, returning a notice
+  // alongside the content when the body could not be decoded faithfully
+  pub fn decode(&self) -> Option<(String, Option<String>)> {
+    let content = self.content.as_ref()?;
+    let label = self.charset();
+    let (encoding, mut notice) =
+      encoding_rs::Encoding::for_label(label.as_bytes()).map_or_else(
+        || {
+          (
+            encoding_rs::UTF_8,
+            Some(format!(
+              "The capsule declared an unknown character encoding ({label}), \
+               so the content was decoded as UTF-8."
+            )),
+          )
+        },
+        |encoding| (encoding, None),
+      );
+    // A byte order mark takes precedence over the declared encoding, and is
+    // stripped from the decoded content
+    let (decoded, encoding, had_errors) = encoding.decode(content);
+
+    if had_errors && notice.is_none() {
+      notice = Some(format!(
+        "The content is not valid {}, so undecodable bytes were replaced.",
+        encoding.name()
+      ));
+    }
+
+    Some((decoded.into_owned(), notice))
+  }
+
+  fn charset(&self) -> String {
+    germ::meta::Meta::from_string(self.meta.as_str())
+      .parameters()
+      .iter()
+      .find(|(key, _)| key.trim().eq_ignore_ascii_case("charset"))
+      .map_or_else(
+        || "utf-8".to_string(),
+        |(_, value)| value.trim().trim_matches('"').to_string(),
+      )
+  }
+
+  pub fn content_bytes(&self) -> Option<&[u8]> { self.content.as_deref() }
+
+  pub fn mime(&self) -> &str {
+    let mime = self.meta.split(';').next().unwrap_or_default().trim();
+
+    if mime.is_empty() { "text/gemini" } else { mime }
+  }
+
+  pub fn is_binary(&self) -> bool {
+    self.status == Status::Success && !self.mime().starts_with("text/")
+  }
+
+  // Bodies which are passed on without being decoded can be sent as they
+  // arrive
+  pub fn is_streamable(&self) -> bool {
+    self.is_binary()
+      || (self.status == Status::Success
+        && self.mime() == "text/plain"
+        && encoding_rs::Encoding::for_label(self.charset().as_bytes())
+          == Some(encoding_rs::UTF_8))
+  }
+
+  pub const fn certificate(&self) -> Option<&tofu::Certificate> {
+    self.certificate.as_ref()
+  }
+
+  fn from_header(header: &[u8]) -> Self {
+    let header = String::from_utf8_lossy(header);
+    let header = header.trim_end();
+    let (status, meta) = header
+      .split_at(header.char_indices().nth(2).map_or(header.len(), |(i, _)| i));
+
+    Self::new(
+      Status::from(status.parse::<i32>().unwrap_or(0)),
+      meta.trim_start().to_string(),
+      None,
+    )
+  }
+
+  pub fn size(&self) -> usize {
+    self.meta.len() + self.content.as_ref().map_or(0, |content| content.len())
+  }
+}
+
+#[derive(Clone)]
+pub struct Redirect {
+  status: Status,
+  target: Url,
+}
+
+impl Redirect {
+  pub fn from_response(
+    url: &Url,
+    response: &Response,
+  ) -> Result<Option<Self>, Error> {
+    if !matches!(
+      response.status(),
+      Status::TemporaryRedirect | Status::PermanentRedirect
+    ) {
+      return Ok(None);
+    }
+
+    Ok(Some(Self {
+      status: *response.status(),
+      target: url.join(response.meta().trim())?,
+    }))
+  }
+
+  pub const fn target(&self) -> &Url { &self.target }
+
+  pub fn is_permanent(&self) -> bool {
+    self.status == Status::PermanentRedirect
+  }
+}
+
+struct Verifier;
+
+impl rustls::client::ServerCertVerifier for Verifier {
+  fn verify_server_cert(
+    &self,
+    _end_entity: &rustls::Certificate,
+    _intermediates: &[rustls::Certificate],
+    _server_name: &rustls::ServerName,
+    _scts: &mut dyn Iterator<Item = &[u8]>,
+    _ocsp_response: &[u8],
+    _now: std::time::SystemTime,
+  ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+    Ok(rustls::client::ServerCertVerified::assertion())
+  }
+}
+
+fn check_size(size: usize) -> std::io::Result<()> {
+  match ENVIRONMENT.max_response_size {
+    limit if limit != 0 && size > limit => Err(std::io::Error::other(format!(
+      "the capsule's response exceeded the maximum size of {limit} bytes"
+    ))),
+    _ => Ok(()),
+  }
+}
+
+async fn read_chunk(stream: &mut Stream) -> std::io::Result<Option<Vec<u8>>> {
+  let mut chunk = vec![0; CHUNK_SIZE];
+
+  match stream.read(&mut chunk).await {
+    Ok(0) => Ok(None),
+    Ok(read) => {
+      chunk.truncate(read);
+
+      Ok(Some(chunk))
+    }
+    // Many capsules close the connection without sending a TLS close_notify
+    Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+    Err(error) => Err(error),
+  }
+}
+
+// A response whose header has been read, but whose body is still waiting on
+// the connection
+struct Connection {
+  response: Response,
+  stream:   Stream,
+  received: Vec<u8>,
+}
+
+impl Connection {
+  async fn buffer(mut self) -> Result<Response, Error> {
+    let mut content = self.received;
+
+    check_size(content.len())?;
+
+    while let Some(chunk) = read_chunk(&mut self.stream).await? {
+      content.extend_from_slice(&chunk);
+
+      check_size(content.len())?;
+    }
+
+    if !content.is_empty() {
+      self.response.content = Some(Arc::from(content));
+    }
+
+    Ok(self.response)
+  }
+
+  fn into_body(self) -> Body {
+    let received = Some(self.received).filter(|received| !received.is_empty());
+
+    Box::pin(futures_util::stream::unfold(
+      Some((self.stream, received, 0)),
+      |state| async move {
+        let (mut stream, received, size) = state?;
+        let chunk = match received {
+          Some(received) => Ok(Some(received)),
+          None => read_chunk(&mut stream).await,
+        };
+
+        match chunk {
+          Ok(Some(chunk)) => {
+            let size = size + chunk.len();
+
+            Some(match check_size(size) {
+              Ok(()) => (Ok(Bytes::from(chunk)), Some((stream, None, size))),
+              Err(error) => (Err(error), None),
+            })
+          }
+          Ok(None) => None,
+          Err(error) => Some((Err(error), None)),
+        }
+      },
+    ))
+  }
+}
+
+async fn connect(
+  url: &Url,
+  identity: Option<&certificate::Identity>,
+) -> Result<Connection, Error> {
+  let host = url
+    .host_str()
+    .ok_or("invalid URL: missing host")?
+    .trim_start_matches('[')
+    .trim_end_matches(']');
+  let builder = rustls::ClientConfig::builder()
+    .with_safe_defaults()
+    .with_custom_certificate_verifier(Arc::new(Verifier));
+  let configuration = match identity {
+    Some(identity) =>
+      builder.with_client_auth_cert(identity.certificates(), identity.key())?,
+    None => builder.with_no_client_auth(),
+  };
+  let port = url.port().unwrap_or(1965);
+  let mut stream = tokio_rustls::TlsConnector::from(Arc::new(configuration))
+    .connect(
+      rustls::ServerName::try_from(host)?,
+      tokio::net::TcpStream::connect((host, port)).await?,
+    )
+    .await?;
+  let certificate = stream
+    .get_ref()
+    .1
+    .peer_certificates()
+    .and_then(<[rustls::Certificate]>::first)
+    .map(|certificate| tofu::check(host, port, &certificate.0))
+    .transpose()?;
+  let mut data = Vec::new();
+
+  stream.write_all(format!("{url}\r\n").as_bytes()).await?;
+
+  let header_end = loop {
+    if let Some(position) = data.windows(2).position(|window| window == b"\r\n")
+    {
+      break position + 2;
+    }
+
+    if data.len() > MAXIMUM_HEADER_SIZE {
+      return Err("invalid response header: meta exceeds 1024 bytes".into());
+    }
+
+    match read_chunk(&mut stream).await? {
+      Some(chunk) => data.extend_from_slice(&chunk),
+      None => break data.len(),
+    }
+  };
+  let mut response = Response::from_header(&data[..header_end]);
+
+  response.certificate = certificate;
+
+  Ok(Connection { response, stream, received: data.split_off(header_end) })
+}
+
+// A body for which `stream` holds is handed back unread, rather than being
+// buffered and cached
+pub async fn request(
+  url: &Url,
+  session: Option<&str>,
+  is_submission: bool,
+  stream: impl Fn(&Response) -> bool + Send + Sync,
+) -> Result<(Response, Option<Body>), Error> {
+  let identity = certificate::for_url(url, session);
+  // Responses to identified requests are personal, and the URLs of submitted
+  // input may hold secrets, so neither is ever cached
+  let is_cacheable = identity.is_none() && !is_submission;
+
+  if is_cacheable {
+    if let Some(response) = cache::CACHE.get(url) {
+      return Ok((response, None));
+    }
+  }
+
+  let connection = connect(url, identity.as_deref()).await?;
+
+  if ENVIRONMENT.stream_responses && stream(&connection.response) {
+    return Ok((connection.response.clone(), Some(connection.into_body())));
+  }
+
+  let response = connection.buffer().await?;
+
+  if is_cacheable {
+    cache::CACHE.insert(url, &response);
+  }
+
+  Ok((response, None))
+}
+
+pub async fn request_following(
+  url: &Url,
+  session: Option<&str>,
+  is_submission: bool,
+  stream: impl Fn(&Response) -> bool + Send + Sync,
+) -> Result<(Response, Vec<Redirect>, Option<Body>), Error> {
+  let mut current = url.clone();
+  let mut redirects: Vec<Redirect> = Vec::new();
+
+  loop {
+    let (response, body) =
+      request(&current, session, is_submission, &stream).await?;
+    let Some(redirect) = Redirect::from_response(&current, &response)? else {
+      return Ok((response, redirects, body));
+    };
+    let target = redirect.target.clone();
+
+    if target == *url
+      || redirects.iter().any(|redirect| redirect.target == target)
+    {
+      return Err(format!("redirect loop detected at {target}").into());
+    }
+
+    if redirects.len() >= ENVIRONMENT.max_redirects {
+      return Err(
+        format!(
+          "too many redirects; gave up after {} at {target}",
+          redirects.len()
+        )
+        .into(),
+      );
+    }
+
+    redirects.push(redirect);
+
+    // Foreign targets such as https:// are left for the HTTP client to follow
+    if target.scheme() != "gemini" {
+      return Ok((response, redirects, body));
+    }
+
+    current = target;
+  }
+}
@@ -0,0 +1,370 @@
+use {
+  super::Response,
+  crate::environment::ENVIRONMENT,
+  germ::request::Status,
+  std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+    sync::{LazyLock, Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+  },
+  url::Url,
+};
+
+pub static CACHE: LazyLock<Cache> = LazyLock::new(|| {
+  Cache::new(
+    Duration::from_secs(ENVIRONMENT.cache_ttl),
+    ENVIRONMENT.cache_capacity,
+    ENVIRONMENT.cache_directory.as_ref().map(PathBuf::from),
+    ENVIRONMENT.cache_directory_capacity,
+  )
+});
+
+struct Entry {
+  response: Response,
+  stored:   SystemTime,
+  tick:     u64,
+}
+
+#[derive(Default)]
+struct State {
+  entries:   HashMap<String, Entry>,
+  recency:   BTreeMap<u64, String>,
+  size:      usize,
+  disk_size: u64,
+  tick:      u64,
+}
+
+impl State {
+  fn remove(&mut self, key: &str) {
+    if let Some(entry) = self.entries.remove(key) {
+      self.recency.remove(&entry.tick);
+
+      self.size -= entry.response.size();
+    }
+  }
+
+  fn touch(&mut self, key: &str) -> Option<&Entry> {
+    self.tick += 1;
+
+    let tick = self.tick;
+    let entry = self.entries.get_mut(key)?;
+
+    self.recency.remove(&entry.tick);
+    self.recency.insert(tick, key.to_string());
+
+    entry.tick = tick;
+
+    Some(entry)
+  }
+}
+
+pub struct Cache {
+  ttl:           Duration,
+  capacity:      usize,
+  directory:     Option<PathBuf>,
+  disk_capacity: u64,
+  state:         Mutex<State>,
+}
+
+impl Cache {
+  fn new(
+    ttl: Duration,
+    capacity: usize,
+    directory: Option<PathBuf>,
+    disk_capacity: u64,
+  ) -> Self {
+    if let Some(directory) = &directory {
+      if !ttl.is_zero() {
+        if let Err(error) = fs::create_dir_all(directory) {
+          warn!(
+            "could not create cache directory {}: {error}",
+            directory.display()
+          );
+        }
+      }
+    }
+
+    let cache = Self {
+      ttl,
+      capacity,
+      directory,
+      disk_capacity,
+      state: Mutex::new(State::default()),
+    };
+
+    if cache.is_enabled() {
+      cache.prune();
+    }
+
+    cache
+  }
+
+  fn state(&self) -> MutexGuard<'_, State> {
+    self.state.lock().unwrap_or_else(PoisonError::into_inner)
+  }
+
+  const fn is_enabled(&self) -> bool { !self.ttl.is_zero() }
+
+  fn is_expired(&self, stored: SystemTime) -> bool {
+    stored.elapsed().map_or(true, |age| age > self.ttl)
+  }
+
+  pub fn get(&self, url: &Url) -> Option<Response> {
+    if !self.is_enabled() {
+      return None;
+    }
+
+    let key = normalise(url);
+    let mut state = self.state();
+
+    if let Some(entry) = state.entries.get(&key) {
+      if self.is_expired(entry.stored) {
+        state.remove(&key);
+        drop(state);
+        self.remove_file(&key);
+
+        return None;
+      }
+
+      return state.touch(&key).map(|entry| entry.response.clone());
+    }
+
+    drop(state);
+
+    let (response, stored) = self.read_file(&key)?;
+
+    if self.is_expired(stored) {
+      self.remove_file(&key);
+
+      return None;
+    }
+
+    self.store(&key, response.clone(), stored);
+
+    Some(response)
+  }
+
+  pub fn insert(&self, url: &Url, response: &Response) {
+    if !self.is_enabled() || !is_cacheable(*response.status()) {
+      return;
+    }
+
+    let key = normalise(url);
+    let stored = SystemTime::now();
+
+    self.write_file(&key, response, stored);
+    self.store(&key, response.clone(), stored);
+  }
+
+  pub fn purge(&self, url: &Url) {
+    let key = normalise(url);
+
+    self.state().remove(&key);
+    self.remove_file(&key);
+  }
+
+  pub fn purge_all(&self) {
+    *self.state() = State::default();
+
+    for (path, ..) in self.files() {
+      if let Err(error) = fs::remove_file(&path) {
+        warn!("could not remove cache file {}: {error}", path.display());
+      }
+    }
+  }
+
+  // Only files named by `file_name` belong to the cache, as the directory may
+  // be shared
+  fn files(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+    let Some(entries) = self
+      .directory
+      .as_ref()
+      .and_then(|directory| fs::read_dir(directory).ok())
+    else {
+      return Vec::new();
+    };
+
+    entries
+      .flatten()
+      .filter(|entry| {
+        entry.file_name().to_str().is_some_and(|name| {
+          name.len() == 16 && name.bytes().all(|byte| byte.is_ascii_hexdigit())
+        })
+      })
+      .filter_map(|entry| {
+        let metadata = entry.metadata().ok()?;
+
+        Some((entry.path(), metadata.len(), metadata.modified().ok()?))
+      })
+      .collect()
+  }
+
+  // Expired files are removed first, then the oldest until the directory is
+  // back to three quarters of its capacity, so that a full directory is not
+  // rescanned on every write
+  fn prune(&self) {
+    let mut files = self.files();
+    let mut size = 0;
+
+    files.retain(|(path, length, modified)| {
+      if self.is_expired(*modified) {
+        let _ = fs::remove_file(path);
+
+        false
+      } else {
+        size += length;
+
+        true
+      }
+    });
+
+    if size > self.disk_capacity {
+      files.sort_by_key(|(_, _, modified)| *modified);
+
+      for (path, length, _) in files {
+        if size <= self.disk_capacity / 4 * 3 {
+          break;
+        }
+
+        if fs::remove_file(&path).is_ok() {
+          size -= length;
+        }
+      }
+    }
+
+    self.state().disk_size = size;
+  }
+
+  fn store(&self, key: &str, response: Response, stored: SystemTime) {
+    let size = response.size();
+
+    if size > self.capacity {
+      return;
+    }
+
+    let mut state = self.state();
+
+    state.remove(key);
+
+    while state.size + size > self.capacity {
+      let Some((_, oldest)) = state.recency.pop_first() else {
+        break;
+      };
+
+      if let Some(entry) = state.entries.remove(&oldest) {
+        state.size -= entry.response.size();
+      }
+    }
+
+    state.size += size;
+
+    state.entries.insert(key.to_string(), Entry { response, stored, tick: 0 });
+    state.touch(key);
+  }
+
+  fn file_path(&self, key: &str) -> Option<PathBuf> {
+    self.directory.as_ref().map(|directory| directory.join(file_name(key)))
+  }
+
+  fn read_file(&self, key: &str) -> Option<(Response, SystemTime)> {
+    let data = fs::read(self.file_path(key)?).ok()?;
+    let mut lines = data.splitn(3, |byte| *byte == b'\n');
+    let stored = std::str::from_utf8(lines.next()?).ok()?.parse().ok()?;
+
+    if lines.next()? != key.as_bytes() {
+      return None;
+    }
+
+    let gemini = lines.next()?;
+    let header_end = gemini.windows(2).position(|window| window == b"\r\n")?;
+    let header = std::str::from_utf8(&gemini[..header_end]).ok()?;
+    let body = &gemini[header_end + 2..];
+    let (status, meta) = header.split_once(' ').unwrap_or((header, ""));
+
+    Some((
+      Response::new(
+        Status::from(status.parse::<i32>().ok()?),
+        meta.to_string(),
+        if body.is_empty() { None } else { Some(body.to_vec()) },
+      ),
+      UNIX_EPOCH + Duration::from_secs(stored),
+    ))
+  }
+
+  fn write_file(&self, key: &str, response: &Response, stored: SystemTime) {
+    let Some(path) = self.file_path(key) else {
+      return;
+    };
+    let mut data = format!(
+      "{}\n{key}\n{} {}\r\n",
+      stored.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+      i32::from(*response.status()),
+      response.meta()
+    )
+    .into_bytes();
+
+    data.extend_from_slice(response.content_bytes().unwrap_or_default());
+
+    let length = data.len() as u64;
+
+    if let Err(error) = fs::write(&path, data) {
+      warn!("could not write cache file {}: {error}", path.display());
+
+      return;
+    }
+
+    let mut state = self.state();
+
+    state.disk_size += length;
+
+    let is_full = state.disk_size > self.disk_capacity;
+
+    drop(state);
+
+    if is_full {
+      self.prune();
+    }
+  }
+
+  fn remove_file(&self, key: &str) {
+    if let Some(path) = self.file_path(key) {
+      let _ = fs::remove_file(path);
+    }
+  }
+}
+
+const fn is_cacheable(status: Status) -> bool {
+  matches!(
+    status,
+    Status::Success
+      | Status::PermanentRedirect
+      | Status::NotFound
+      | Status::Gone
+  )
+}
+
+pub fn normalise(url: &Url) -> String {
+  let mut url = url.clone();
+
+  url.set_fragment(None);
+
+  if url.port() == Some(1965) {
+    let _ = url.set_port(None);
+  }
+
+  if url.path().is_empty() {
+    url.set_path("/");
+  }
+
+  url.to_string()
+}
+
+fn file_name(key: &str) -> String {
+  let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+    (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+  });
+
+  format!("{hash:016x}")
+}
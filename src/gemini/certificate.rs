@@ -0,0 +1,212 @@
+use {
+  crate::{environment::ENVIRONMENT, url::matches_pattern},
+  std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+  },
+  url::Url,
+};
+
+static IDENTITIES: LazyLock<Vec<(String, Arc<Identity>)>> =
+  LazyLock::new(|| {
+    ENVIRONMENT
+      .client_certificates
+      .iter()
+      .filter_map(|(pattern, certificate, key)| {
+        match Identity::from_files(certificate, key) {
+          Ok(identity) => Some((pattern.clone(), Arc::new(identity))),
+          Err(error) => {
+            warn!(
+              "could not load client certificate {certificate} for {pattern}: \
+               {error}"
+            );
+
+            None
+          }
+        }
+      })
+      .collect()
+  });
+
+static SESSIONS: LazyLock<Mutex<HashMap<String, Session>>> =
+  LazyLock::new(Mutex::default);
+
+struct Session {
+  identities: HashMap<String, (Arc<Identity>, Instant)>,
+  last_used:  Instant,
+}
+
+pub struct Identity {
+  certificates: Vec<rustls::Certificate>,
+  key:          rustls::PrivateKey,
+}
+
+impl Identity {
+  pub const fn new(
+    certificates: Vec<rustls::Certificate>,
+    key: rustls::PrivateKey,
+  ) -> Self {
+    Self { certificates, key }
+  }
+
+  fn from_files(certificate: &str, key: &str) -> Result<Self, super::Error> {
+    let certificates = rustls_pemfile::certs(&mut std::io::BufReader::new(
+      std::fs::File::open(certificate)?,
+    ))?
+    .into_iter()
+    .map(rustls::Certificate)
+    .collect::<Vec<_>>();
+    let key = rustls_pemfile::read_all(&mut std::io::BufReader::new(
+      std::fs::File::open(key)?,
+    ))?
+    .into_iter()
+    .find_map(|item| match item {
+      rustls_pemfile::Item::PKCS8Key(key)
+      | rustls_pemfile::Item::RSAKey(key)
+      | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+      _ => None,
+    })
+    .ok_or("no private key found")?;
+
+    if certificates.is_empty() {
+      return Err("no certificate found".into());
+    }
+
+    Ok(Self::new(certificates, key))
+  }
+
+  fn generate() -> Result<Self, super::Error> {
+    let key_pair = rcgen::KeyPair::generate()?;
+    let mut parameters = rcgen::CertificateParams::new(Vec::new())?;
+
+    parameters
+      .distinguished_name
+      .push(rcgen::DnType::CommonName, "September Visitor");
+
+    let certificate = parameters.self_signed(&key_pair)?;
+
+    Ok(Self::new(
+      vec![rustls::Certificate(certificate.der().to_vec())],
+      rustls::PrivateKey(key_pair.serialize_der()),
+    ))
+  }
+
+  pub fn certificates(&self) -> Vec<rustls::Certificate> {
+    self.certificates.clone()
+  }
+
+  pub fn key(&self) -> rustls::PrivateKey { self.key.clone() }
+}
+
+fn sessions() -> MutexGuard<'static, HashMap<String, Session>> {
+  let mut sessions = SESSIONS.lock().unwrap_or_else(PoisonError::into_inner);
+  let lifetime = Duration::from_secs(ENVIRONMENT.ephemeral_lifetime);
+
+  sessions.retain(|_, session| session.last_used.elapsed() < lifetime);
+
+  sessions
+}
+
+fn capsule(url: &Url) -> Option<String> {
+  Some(format!("{}:{}", url.host_str()?, url.port().unwrap_or(1965)))
+}
+
+pub fn new_session() -> Result<String, super::Error> {
+  let mut bytes = [0u8; 32];
+
+  ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut bytes)
+    .map_err(|_| "could not generate a session identifier")?;
+
+  Ok(bytes.iter().fold(String::new(), |mut session, byte| {
+    let _ = write!(session, "{byte:02x}");
+
+    session
+  }))
+}
+
+pub fn ephemeral(session: &str, url: &Url) -> Option<Arc<Identity>> {
+  let capsule = capsule(url)?;
+
+  sessions().get_mut(session).and_then(|session| {
+    session.last_used = Instant::now();
+
+    session.identities.get(&capsule).map(|(identity, _)| identity.clone())
+  })
+}
+
+// Identities are only evicted from the requesting session, so that one visitor
+// can never discard another's. Once every session together holds the global
+// limit, new identities are refused instead, and `false` is returned.
+pub fn create_ephemeral(
+  session: &str,
+  url: &Url,
+) -> Result<bool, super::Error> {
+  let capsule = capsule(url).ok_or("invalid URL: missing host")?;
+  let identity = Arc::new(Identity::generate()?);
+  let mut sessions = sessions();
+  let mut total =
+    sessions.values().map(|session| session.identities.len()).sum::<usize>();
+  let identities = &mut sessions
+    .entry(session.to_string())
+    .or_insert_with(|| Session {
+      identities: HashMap::new(),
+      last_used:  Instant::now(),
+    })
+    .identities;
+
+  if !identities.contains_key(&capsule) {
+    while identities.len() >= ENVIRONMENT.ephemeral_session_capacity.max(1) {
+      let Some(oldest) = identities
+        .iter()
+        .min_by_key(|(_, (_, created))| *created)
+        .map(|(capsule, _)| capsule.clone())
+      else {
+        break;
+      };
+
+      identities.remove(&oldest);
+
+      total -= 1;
+    }
+
+    if total >= ENVIRONMENT.ephemeral_capacity {
+      if identities.is_empty() {
+        sessions.remove(session);
+      }
+
+      return Ok(false);
+    }
+  }
+
+  identities.insert(capsule, (identity, Instant::now()));
+
+  drop(sessions);
+
+  Ok(true)
+}
+
+pub fn delete_ephemeral(session: &str, url: &Url) {
+  let Some(capsule) = capsule(url) else {
+    return;
+  };
+  let mut sessions = sessions();
+
+  if let Some(entry) = sessions.get_mut(session) {
+    entry.identities.remove(&capsule);
+
+    if entry.identities.is_empty() {
+      sessions.remove(session);
+    }
+  }
+}
+
+pub fn for_url(url: &Url, session: Option<&str>) -> Option<Arc<Identity>> {
+  session.and_then(|session| ephemeral(session, url)).or_else(|| {
+    IDENTITIES
+      .iter()
+      .find(|(pattern, _)| matches_pattern(pattern, url.as_str()))
+      .map(|(_, identity)| identity.clone())
+  })
+}
@@ -0,0 +1,233 @@
+use {
+  crate::environment::ENVIRONMENT,
+  std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    fs,
+    sync::{LazyLock, Mutex, PoisonError},
+  },
+};
+
+static STORE: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(|| {
+  let mut store = HashMap::new();
+
+  if let Some(path) = &ENVIRONMENT.tofu_store {
+    if let Ok(contents) = fs::read_to_string(path) {
+      for line in contents.lines() {
+        if let Some((host, fingerprint)) = line.split_once(' ') {
+          store.insert(host.to_string(), fingerprint.trim().to_string());
+        }
+      }
+    }
+  }
+
+  Mutex::new(store)
+});
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Trust {
+  FirstUse,
+  Matched,
+  Pinned,
+  Changed,
+}
+
+impl fmt::Display for Trust {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Self::FirstUse => "Trusted on first use",
+      Self::Matched => "Matches the certificate trusted on first use",
+      Self::Pinned => "Matches the pinned certificate",
+      Self::Changed => "Does not match the certificate trusted on first use",
+    })
+  }
+}
+
+#[derive(Clone)]
+pub struct Certificate {
+  fingerprint: String,
+  expiry:      Option<String>,
+  trust:       Trust,
+}
+
+impl Certificate {
+  pub fn fingerprint(&self) -> &str { &self.fingerprint }
+
+  pub fn expiry(&self) -> Option<&str> { self.expiry.as_deref() }
+
+  pub const fn trust(&self) -> Trust { self.trust }
+}
+
+fn fingerprint(der: &[u8]) -> String {
+  ring::digest::digest(&ring::digest::SHA256, der).as_ref().iter().fold(
+    String::new(),
+    |mut fingerprint, byte| {
+      let _ = write!(fingerprint, "{byte:02x}");
+
+      fingerprint
+    },
+  )
+}
+
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+  let tag = *data.first()?;
+  let length_byte = *data.get(1)?;
+  let (length, offset) = if length_byte < 0x80 {
+    (usize::from(length_byte), 2)
+  } else {
+    let count = usize::from(length_byte & 0x7f);
+
+    if count > 4 {
+      return None;
+    }
+
+    (
+      data
+        .get(2..2 + count)?
+        .iter()
+        .fold(0, |length, byte| (length << 8) | usize::from(*byte)),
+      2 + count,
+    )
+  };
+  let end = offset.checked_add(length)?;
+
+  Some((tag, data.get(offset..end)?, data.get(end..)?))
+}
+
+// Walks just far enough into the X.509 structure to read
+// `tbsCertificate.validity.notAfter`
+fn expiry(der: &[u8]) -> Option<String> {
+  let (_, certificate, _) = der_element(der)?;
+  let (_, mut fields, _) = der_element(certificate)?;
+  let (tag, _, after_version) = der_element(fields)?;
+
+  if tag == 0xa0 {
+    fields = after_version;
+  }
+
+  // Skip the serial number, signature algorithm, and issuer
+  for _ in 0..3 {
+    fields = der_element(fields)?.2;
+  }
+
+  let (_, validity, _) = der_element(fields)?;
+  let (_, _, not_after) = der_element(validity)?;
+  let (tag, time, _) = der_element(not_after)?;
+  let time = std::str::from_utf8(time).ok()?;
+
+  // The time comes from the capsule, so anything other than the digits of a
+  // `UTCTime` or `GeneralizedTime` is rejected
+  if !time
+    .get(..if tag == 0x17 { 12 } else { 14 })?
+    .bytes()
+    .all(|byte| byte.is_ascii_digit())
+  {
+    return None;
+  }
+
+  let (year, time) = if tag == 0x17 {
+    let year = time.get(0..2)?.parse::<u32>().ok()?;
+
+    (if year >= 50 { 1900 + year } else { 2000 + year }, time.get(2..)?)
+  } else {
+    (time.get(0..4)?.parse().ok()?, time.get(4..)?)
+  };
+
+  Some(format!(
+    "{year}-{}-{} {}:{}:{} UTC",
+    time.get(0..2)?,
+    time.get(2..4)?,
+    time.get(4..6)?,
+    time.get(6..8)?,
+    time.get(8..10)?
+  ))
+}
+
+fn persist(store: &HashMap<String, String>) {
+  let Some(path) = &ENVIRONMENT.tofu_store else {
+    return;
+  };
+  let mut contents = store
+    .iter()
+    .map(|(host, fingerprint)| format!("{host} {fingerprint}"))
+    .collect::<Vec<_>>();
+
+  contents.sort();
+
+  if let Err(error) = fs::write(path, contents.join("\n") + "\n") {
+    warn!("could not write TOFU store {path}: {error}");
+  }
+}
+
+// An empty fingerprint pins whichever certificate is trusted on first use
+fn pin(host: &str) -> Option<&str> {
+  ENVIRONMENT.tofu_pins.iter().find_map(|(pinned_host, fingerprint)| {
+    let matches = if pinned_host.contains(':') {
+      pinned_host == host
+    } else {
+      host.strip_suffix(":1965") == Some(pinned_host.as_str())
+    };
+
+    matches.then_some(fingerprint.as_str())
+  })
+}
+
+pub fn check(
+  host: &str,
+  port: u16,
+  der: &[u8],
+) -> Result<Certificate, super::Error> {
+  let host = format!("{host}:{port}");
+  let fingerprint = fingerprint(der);
+  let expiry = expiry(der);
+  let pin = pin(&host);
+
+  if let Some(pinned) = pin.filter(|pinned| !pinned.is_empty()) {
+    if !pinned.eq_ignore_ascii_case(&fingerprint) {
+      return Err(
+        format!(
+          "the certificate presented by {host} ({fingerprint}) does not match \
+           its pinned certificate ({pinned})"
+        )
+        .into(),
+      );
+    }
+
+    return Ok(Certificate { fingerprint, expiry, trust: Trust::Pinned });
+  }
+
+  let mut store = STORE.lock().unwrap_or_else(PoisonError::into_inner);
+  let trust = match store.get(&host) {
+    Some(known) if *known == fingerprint => Trust::Matched,
+    Some(known) => {
+      if ENVIRONMENT.tofu_refuse || pin.is_some() {
+        return Err(
+          format!(
+            "the certificate presented by {host} ({fingerprint}) does not \
+             match the certificate trusted on first use ({known})"
+          )
+          .into(),
+        );
+      }
+
+      warn!(
+        "the certificate presented by {host} changed from {known} to \
+         {fingerprint}"
+      );
+
+      Trust::Changed
+    }
+    None => Trust::FirstUse,
+  };
+
+  // A changed certificate never replaces the trusted one, so that it keeps
+  // being reported until an operator removes the entry
+  if trust == Trust::FirstUse {
+    store.insert(host, fingerprint.clone());
+    persist(&store);
+  }
+
+  drop(store);
+
+  Ok(Certificate { fingerprint, expiry, trust })
+}
@@ -1,3 +1,11 @@
+mod ansi;
+pub mod escape;
+mod heading;
+mod highlight;
+mod inline;
+mod preformatted;
+mod text;
+
 use {
   crate::{environment::ENVIRONMENT, url::matches_pattern},
   germ::ast::Node,
@@ -11,273 +19,483 @@ fn link_from_host_href(url: &Url, href: &str) -> Option<String> {
   } else {
     Some(format!(
       "gemini://{}{}{}",
-      url.domain()?,
+      url.host_str()?,
       { if href.starts_with('/') { "" } else { "/" } },
       href
     ))
   }
 }
 
-fn safe(text: &str) -> String {
-  let is_ordered_list = text.starts_with(|c: char| c.is_ascii_digit())
-    && text.get(1..3) == Some(". ");
+pub fn from_response(
+  response: &crate::gemini::Response,
+  content: &str,
+  url: &Url,
+  configuration: &crate::response::configuration::Configuration,
+) -> Option<(String, String)> {
+  if *response.status() != germ::request::Status::Success {
+    return from_gemini(content, url, configuration);
+  }
 
-  if is_ordered_list {
-    text.to_string()
-  } else {
-    comrak::markdown_to_html(text, &comrak::ComrakOptions::default())
-      .replace("<p>", "")
-      .replace("</p>", "")
+  match response.mime() {
+    "text/gemini" => from_gemini(content, url, configuration),
+    "text/markdown" | "text/x-markdown" => Some(text::markdown(content)),
+    "text/csv" => Some((String::new(), text::csv(content, b','))),
+    "text/tab-separated-values" =>
+      Some((String::new(), text::csv(content, b'\t'))),
+    // `text/plain` and any text type without a dedicated renderer
+    _ => Some((String::new(), text::plain(content))),
   }
 }
 
-#[allow(clippy::too_many_lines, clippy::cognitive_complexity)]
-pub fn from_gemini(
-  response: &germ::request::Response,
-  url: &Url,
-  configuration: &crate::response::configuration::Configuration,
-) -> Option<(String, String)> {
-  const GEMINI_FRAGMENT: &str =
-    r#"<span class="gemini-fragment">=&#62; </span>"#;
-  let ast_tree = germ::ast::Ast::from_string(
-    response.content().as_ref().map_or_else(String::default, String::clone),
-  );
-  let ast = ast_tree.inner();
-  let mut html = String::new();
-  let mut title = String::new();
-  let mut previous_link = false;
-  let mut previous_link_count = 0;
-  let condense_links =
-    ENVIRONMENT.condense_links.contains(&url.path().to_string())
-      || ENVIRONMENT.condense_links.contains(&"*".to_string());
-  let condensible_headings = ENVIRONMENT
-    .condense_links_at_headings
-    .iter()
-    .map(String::as_str)
-    .collect::<Vec<_>>();
-  let mut in_condense_links_flag_trap = !condensible_headings.is_empty();
-
-  for node in ast {
-    if condensible_headings.contains(&node.to_gemtext().as_str()) {
-      in_condense_links_flag_trap = true;
+const GEMINI_FRAGMENT: &str = r#"<span class="gemini-fragment">=&#62; </span>"#;
+const LINK_SEPARATOR: &str = r#" <span class="gemini-fragment">|</span> "#;
+
+// A run of adjacent links, split into lines of links which are condensed
+// together, and only written out once the run ends
+#[derive(Default)]
+struct LinkGroup {
+  lines: Vec<Vec<String>>,
+}
+
+impl LinkGroup {
+  fn push(&mut self, link: String, condense: bool) {
+    match self.lines.last_mut() {
+      Some(links) if condense => links.push(link),
+      _ => self.lines.push(vec![link]),
     }
+  }
 
-    let align_adjacent_links = |html: &str| {
-      if previous_link_count > 0 {
-        html.rfind(GEMINI_FRAGMENT).map_or_else(
-          || html.to_string(),
-          |position| {
-            let mut result =
-              String::with_capacity(html.len() - GEMINI_FRAGMENT.len());
+  fn flush(&mut self, html: &mut String) {
+    if self.lines.is_empty() {
+      return;
+    }
 
-            result.push_str(&html[..position]);
-            result.push_str(&html[position + GEMINI_FRAGMENT.len()..]);
+    html.push_str("<p>");
 
-            result
-          },
-        )
+    for (index, line) in self.lines.drain(..).enumerate() {
+      if index > 0 {
+        html.push_str("<br />");
+      }
+
+      html.push_str(GEMINI_FRAGMENT);
+      html.push_str(&line.join(LINK_SEPARATOR));
+    }
+
+    html.push_str("</p>");
+  }
+}
+
+pub fn resolve_link(
+  url: &Url,
+  configuration: &crate::response::configuration::Configuration,
+  to: &str,
+) -> Option<String> {
+  let mut href = to.to_string();
+  let mut surface = false;
+
+  if href.starts_with("./") || href.starts_with("../") {
+    if let Ok(url) = url.join(&href) {
+      href = url.to_string();
+    }
+  }
+
+  if href.contains("://") && !href.starts_with("gemini://") {
+    surface = true;
+  } else if !href.contains("://") && href.contains(':') {
+    // href contains a scheme-like pattern (e.g., mailto:), keep as-is
+  } else if !href.starts_with("gemini://") && !href.starts_with('/') {
+    href = format!(
+      "{}/{}",
+      url.host_str()?,
+      if url.path().ends_with('/') {
+        format!("{}{}", url.path(), href)
       } else {
-        html.to_string()
+        format!("{}/{}", url.path(), href)
       }
-    };
+    )
+    .replace("//", "/");
+    href = format!("gemini://{href}");
+  } else if href.starts_with('/') || !href.contains("://") {
+    href = link_from_host_href(url, &href)?;
+  }
 
-    if previous_link
-      && (!matches!(node, Node::Link { .. })
-        || (!condense_links && !in_condense_links_flag_trap))
+  if ENVIRONMENT.proxy_by_default && href.contains("gemini://") && !surface {
+    let host = url.host()?.to_string();
+
+    if configuration.is_proxy()
+      || configuration.is_no_css()
+      || href
+        .trim_start_matches("gemini://")
+        .trim_end_matches('/')
+        .split('/')
+        .next()
+        != Some(host.as_str())
     {
-      if let Some(next) = ast.iter().skip_while(|n| n != &node).nth(1) {
-        if matches!(next, Node::Link { .. }) || previous_link {
-          html.push_str("<br />");
-        } else {
-          html.push_str("</p>");
+      href = format!(
+        "/{}/{}",
+        if configuration.is_no_css() { "nocss" } else { "proxy" },
+        href.trim_start_matches("gemini://")
+      );
+    } else {
+      href = href.trim_start_matches("gemini://").replacen(&host, "", 1);
+    }
+  }
+
+  if let Some(patterns) = &ENVIRONMENT.keep_gemini {
+    if (href.starts_with('/') || !href.contains("://")) && !surface {
+      let temporary_href = link_from_host_href(url, &href)?;
+      let should_exclude = patterns
+        .iter()
+        .filter(|p| p.starts_with('!'))
+        .any(|p| matches_pattern(&p[1..], &temporary_href));
+
+      if !should_exclude {
+        let should_include = patterns
+          .iter()
+          .filter(|p| !p.starts_with('!'))
+          .any(|p| matches_pattern(p, &temporary_href));
+
+        if should_include {
+          href = temporary_href;
         }
-      } else {
-        html.push_str("</p>");
       }
+    }
+  }
 
-      previous_link = false;
-      html = align_adjacent_links(&html);
-      previous_link_count = 0;
-    } else if previous_link {
-      html = align_adjacent_links(&html);
+  Some(href)
+}
 
-      html.push_str(r#" <span class="gemini-fragment">|</span> "#);
+#[derive(Clone, Copy)]
+enum Embed {
+  Image,
+  Audio,
+  Video,
+}
+
+impl Embed {
+  fn from_href(href: &str) -> Option<Self> {
+    let extension =
+      std::path::Path::new(href).extension()?.to_str()?.to_lowercase();
+    // Browsers cannot play media over Gemini, so only proxied media is embedded
+    let embed_media =
+      ENVIRONMENT.embed_media.is_some() && !href.starts_with("gemini://");
 
-      previous_link_count += 1;
-    } else if !previous_link && matches!(node, Node::Link { .. }) {
-      html.push_str("<p>");
+    match extension.as_str() {
+      "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg"
+        if ENVIRONMENT.embed_images.is_some() =>
+        Some(Self::Image),
+      "mp3" | "ogg" | "opus" | "flac" | "wav" if embed_media =>
+        Some(Self::Audio),
+      "mp4" | "webm" if embed_media => Some(Self::Video),
+      _ => None,
     }
+  }
 
-    match node {
-      Node::Text(text) => {
-        let _ = write!(&mut html, "<p>{}</p>", safe(text));
+  fn keeps_link(self) -> bool {
+    match self {
+      Self::Image => ENVIRONMENT.embed_images.as_deref() == Some("1"),
+      Self::Audio | Self::Video =>
+        ENVIRONMENT.embed_media.as_deref() == Some("1"),
+    }
+  }
+
+  fn element(self, href: &str, text: &str) -> String {
+    let source = escape::href(href);
+
+    match self {
+      Self::Image => format!(
+        "<p><img src=\"{source}\" alt=\"{}\" /></p>",
+        escape::attribute(text)
+      ),
+      Self::Audio | Self::Video => {
+        let (tag, preload) = if matches!(self, Self::Audio) {
+          ("audio", "none")
+        } else {
+          ("video", "metadata")
+        };
+
+        format!(
+          "<p><{tag} controls preload=\"{preload}\" src=\"{source}\"><a \
+           href=\"{source}\">{}</a></{tag}></p>",
+          escape::text(text)
+        )
       }
-      Node::Link { to, text } => {
-        let mut href = to.clone();
-        let mut surface = false;
-
-        if href.starts_with("./") || href.starts_with("../") {
-          if let Ok(url) = url.join(&href) {
-            href = url.to_string();
-          }
-        }
+    }
+  }
+}
 
-        if href.contains("://") && !href.starts_with("gemini://") {
-          surface = true;
-        } else if !href.contains("://") && href.contains(':') {
-          // href contains a scheme-like pattern (e.g., mailto:), keep as-is
-        } else if !href.starts_with("gemini://") && !href.starts_with('/') {
-          href = format!(
-            "{}/{}",
-            url.domain().unwrap(),
-            if url.path().ends_with('/') {
-              format!("{}{}", url.path(), href)
-            } else {
-              format!("{}/{}", url.path(), href)
-            }
-          )
-          .replace("//", "/");
-          href = format!("gemini://{href}");
-        } else if href.starts_with('/') || !href.contains("://") {
-          href = link_from_host_href(url, &href)?;
-        }
+// Every form a page is rendered in is driven by the same walk over its nodes,
+// and only decides how each node is written
+pub trait Output {
+  fn text(&mut self, text: &str);
 
-        if ENVIRONMENT.proxy_by_default
-          && href.contains("gemini://")
-          && !surface
-        {
-          if (configuration.is_proxy())
-            || configuration.is_no_css()
-            || href
-              .trim_start_matches("gemini://")
-              .trim_end_matches('/')
-              .split('/')
-              .collect::<Vec<_>>()
-              .first()
-              .unwrap()
-              != &url.host().unwrap().to_string().as_str()
-          {
-            href = format!(
-              "/{}/{}",
-              if configuration.is_no_css() { "nocss" } else { "proxy" },
-              href.trim_start_matches("gemini://")
-            );
-          } else {
-            href = href.trim_start_matches("gemini://").replacen(
-              &if let Some(host) = url.host() {
-                host.to_string()
-              } else {
-                return None;
-              },
-              "",
-              1,
-            );
-          }
-        }
+  fn heading(&mut self, level: usize, text: &str);
 
-        if let Some(patterns) = &ENVIRONMENT.keep_gemini {
-          if (href.starts_with('/') || !href.contains("://")) && !surface {
-            let temporary_href = link_from_host_href(url, &href)?;
-            let should_exclude = patterns
-              .iter()
-              .filter(|p| p.starts_with('!'))
-              .any(|p| matches_pattern(&p[1..], &temporary_href));
-
-            if !should_exclude {
-              let should_include = patterns
-                .iter()
-                .filter(|p| !p.starts_with('!'))
-                .any(|p| matches_pattern(p, &temporary_href));
-
-              if should_include {
-                href = temporary_href;
-              }
-            }
-          }
-        }
+  fn list(&mut self, items: &[String]);
 
-        if let Some(embed_images) = &ENVIRONMENT.embed_images {
-          if let Some(extension) = std::path::Path::new(&href).extension() {
-            if extension == "png"
-              || extension == "jpg"
-              || extension == "jpeg"
-              || extension == "gif"
-              || extension == "webp"
-              || extension == "svg"
-            {
-              if embed_images == "1" {
-                let _ = writeln!(
-                  &mut html,
-                  "<p><a href=\"{}\">{}</a> <i>Embedded below</i></p>",
-                  href,
-                  safe(text.as_ref().unwrap_or(to)),
-                );
-              }
-
-              let _ = writeln!(
-                &mut html,
-                "<p><img src=\"{}\" alt=\"{}\" /></p>",
-                safe(&href),
-                safe(text.as_ref().unwrap_or(to)),
-              );
-
-              continue;
-            }
-          }
-        }
+  fn blockquote(&mut self, text: &str);
 
-        previous_link = true;
+  fn preformatted(&mut self, text: &str, alt_text: Option<&str>);
 
-        let _ = write!(
-          &mut html,
-          r#"{}<a href="{}">{}</a>"#,
-          GEMINI_FRAGMENT,
-          href,
-          safe(text.as_ref().unwrap_or(to)).trim(),
-        );
-      }
-      Node::Heading { level, text } => {
-        if !condensible_headings.contains(&node.to_gemtext().as_str()) {
-          in_condense_links_flag_trap = false;
-        }
+  fn link(&mut self, to: &str, text: Option<&str>) -> Option<()>;
 
-        if title.is_empty() && *level == 1 {
-          title = safe(text);
-        }
+  fn whitespace(&mut self);
+}
 
-        let _ = write!(
-          &mut html,
-          "<{}>{}</{0}>",
-          match level {
-            1 => "h1",
-            2 => "h2",
-            3 => "h3",
-            _ => "p",
-          },
-          safe(text),
-        );
+pub fn walk(content: &str, output: &mut impl Output) -> Option<()> {
+  for node in germ::ast::Ast::from_string(content).inner() {
+    match node {
+      Node::Text(text) => output.text(text),
+      Node::Heading { level, text } => output.heading(*level, text),
+      Node::List(items) => output.list(items),
+      Node::Blockquote(text) => output.blockquote(text),
+      Node::PreformattedText { alt_text, text } =>
+        output.preformatted(text, alt_text.as_deref()),
+      Node::Link { to, text } => output.link(to, text.as_deref())?,
+      Node::Whitespace => output.whitespace(),
+    }
+  }
+
+  Some(())
+}
+
+struct HtmlOutput<'a> {
+  url:                         &'a Url,
+  configuration: &'a crate::response::configuration::Configuration,
+  html:                        String,
+  title:                       String,
+  links:                       LinkGroup,
+  condense_links:              bool,
+  in_condense_links_flag_trap: bool,
+  markup:                      inline::Markup,
+  headings:                    heading::Headings,
+}
+
+impl Output for HtmlOutput<'_> {
+  fn text(&mut self, text: &str) {
+    self.links.flush(&mut self.html);
+
+    let _ =
+      write!(&mut self.html, "<p>{}</p>", inline::render(text, self.markup));
+  }
+
+  fn heading(&mut self, level: usize, text: &str) {
+    self.links.flush(&mut self.html);
+
+    self.in_condense_links_flag_trap = ENVIRONMENT
+      .condense_links_at_headings
+      .contains(&Node::Heading { level, text: text.to_string() }.to_gemtext());
+
+    if self.title.is_empty() && level == 1 {
+      self.title = escape::text(text);
+    }
+
+    let element =
+      self.headings.element(level, text, &inline::render(text, self.markup));
+
+    self.html.push_str(&element);
+  }
+
+  fn list(&mut self, items: &[String]) {
+    self.links.flush(&mut self.html);
+    self.html.push_str("<ul>");
+
+    for (index, item) in items.iter().enumerate() {
+      if index > 0 {
+        self.html.push('\n');
       }
-      Node::List(items) => {
-        let _ = write!(
-          &mut html,
-          "<ul>{}</ul>",
-          items
-            .iter()
-            .map(|i| format!("<li>{}</li>", safe(i)))
-            .collect::<Vec<String>>()
-            .join("\n")
+
+      let _ = write!(
+        &mut self.html,
+        "<li>{}</li>",
+        inline::render(item, self.markup)
+      );
+    }
+
+    self.html.push_str("</ul>");
+  }
+
+  fn blockquote(&mut self, text: &str) {
+    self.links.flush(&mut self.html);
+
+    let _ = write!(
+      &mut self.html,
+      "<blockquote>{}</blockquote>",
+      inline::render(text, self.markup)
+    );
+  }
+
+  fn preformatted(&mut self, text: &str, alt_text: Option<&str>) {
+    self.links.flush(&mut self.html);
+    self.html.push_str(&preformatted::render(text, alt_text));
+  }
+
+  fn link(&mut self, to: &str, text: Option<&str>) -> Option<()> {
+    let href = resolve_link(self.url, self.configuration, to)?;
+    let text = text.unwrap_or(to);
+
+    if let Some(embed) = Embed::from_href(&href) {
+      self.links.flush(&mut self.html);
+
+      if embed.keeps_link() {
+        let _ = writeln!(
+          &mut self.html,
+          "<p><a href=\"{}\">{}</a> <i>Embedded below</i></p>",
+          escape::href(&href),
+          inline::render(text, self.markup),
         );
       }
-      Node::Blockquote(text) => {
-        let _ = write!(&mut html, "<blockquote>{}</blockquote>", safe(text));
-      }
-      Node::PreformattedText { text, .. } => {
-        let new_text = text.strip_suffix('\n').unwrap_or(text);
-        let _ = write!(&mut html, "<pre>{new_text}</pre>");
-      }
-      Node::Whitespace => {}
+
+      let _ = writeln!(&mut self.html, "{}", embed.element(&href, text));
+
+      return Some(());
     }
+
+    let link = format!(
+      r#"<a href="{}">{}</a>"#,
+      escape::href(&href),
+      inline::render(text, self.markup).trim()
+    );
+
+    self
+      .links
+      .push(link, self.condense_links || self.in_condense_links_flag_trap);
+
+    Some(())
   }
 
-  Some((title, html))
+  fn whitespace(&mut self) { self.links.flush(&mut self.html); }
+}
+
+pub fn from_gemini(
+  content: &str,
+  url: &Url,
+  configuration: &crate::response::configuration::Configuration,
+) -> Option<(String, String)> {
+  let mut output = HtmlOutput {
+    url,
+    configuration,
+    html: String::new(),
+    title: String::new(),
+    links: LinkGroup::default(),
+    condense_links: ENVIRONMENT
+      .condense_links
+      .iter()
+      .any(|path| path == url.path() || path == "*"),
+    in_condense_links_flag_trap: !ENVIRONMENT
+      .condense_links_at_headings
+      .is_empty(),
+    markup: inline::Markup::for_url(url),
+    headings: heading::Headings::default(),
+  };
+
+  walk(content, &mut output)?;
+
+  output.links.flush(&mut output.html);
+
+  if let Some(table_of_contents) = output.headings.table_of_contents(url) {
+    output.html.insert_str(0, &table_of_contents);
+  }
+
+  Some((output.title, output.html))
+}
+
+#[cfg(test)]
+mod tests {
+  fn render(gemtext: &str) -> String {
+    super::from_gemini(
+      gemtext,
+      &url::Url::parse("gemini://fuwn.me/").unwrap(),
+      &crate::response::configuration::Configuration::new(),
+    )
+    .unwrap()
+    .1
+  }
+
+  fn assert_inert(gemtext: &str) {
+    let html = render(gemtext);
+
+    for needle in
+      ["<script", "<img", "onerror=", "javascript:", "\"onmouseover"]
+    {
+      assert!(!html.contains(needle), "{needle} in {html}");
+    }
+  }
+
+  // A gemlog index: long runs of links broken up by the occasional heading
+  // and paragraph
+  fn gemlog_index(lines: usize) -> String {
+    (0..lines)
+      .map(|line| match line % 100 {
+        0 => format!("## {line}"),
+        1 => format!("Entries from {line} onwards"),
+        _ => format!("=> /gemlog/{line}.gmi Entry {line}"),
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  fn fastest_render(gemtext: &str) -> std::time::Duration {
+    (0..5)
+      .map(|_| {
+        let start = std::time::Instant::now();
+
+        render(gemtext);
+
+        start.elapsed()
+      })
+      .min()
+      .unwrap_or_default()
+  }
+
+  // Quadrupling the document quadruples the work of a linear renderer, but
+  // multiplies that of a quadratic one by sixteen
+  #[test]
+  #[ignore = "benchmark; run with `cargo test --release -- --ignored`"]
+  fn benchmark_large_document() {
+    let small = fastest_render(&gemlog_index(2_500));
+    let large = fastest_render(&gemlog_index(10_000));
+
+    assert!(
+      large < small * 8,
+      "10000 lines took {large:?}, but 2500 lines took {small:?}"
+    );
+  }
+
+  #[test]
+  fn resolves_links_on_ip_literal_hosts() {
+    for host in ["127.0.0.1", "[::1]"] {
+      let (_, html) = super::from_gemini(
+        "=> /root Root\n=> relative Relative",
+        &url::Url::parse(&format!("gemini://{host}/dir/")).unwrap(),
+        &crate::response::configuration::Configuration::new(),
+      )
+      .unwrap();
+
+      assert!(html.contains("Root</a>") && html.contains("Relative</a>"));
+    }
+  }
+
+  #[test]
+  fn escapes_preformatted_text() {
+    assert_inert("```\n</pre><script>alert(1)</script>\n```");
+  }
+
+  #[test]
+  fn escapes_text_lines() {
+    assert_inert("<script>alert(1)</script>");
+    assert_inert("1. <script>alert(1)</script>");
+    assert_inert("# <img src=x onerror=alert(1)>");
+    assert_inert("* <img src=x onerror=alert(1)>");
+    assert_inert("> <script>alert(1)</script>");
+  }
+
+  #[test]
+  fn escapes_links() {
+    assert_inert("=> javascript:alert(1) Click me");
+    assert_inert("=> JaVaScRiPt:alert(1)");
+    assert_inert("=> \"><script>alert(1)</script> Quoted");
+    assert_inert("=> /path <script>alert(1)</script>");
+    assert_inert("=> gemini://fuwn.me/\"onmouseover=\"alert(1) Attribute");
+  }
 }
@@ -0,0 +1,243 @@
+use {super::escape, crate::environment::ENVIRONMENT, std::fmt::Write};
+
+// The standard and bright colours follow the base16 terminal mapping, so they
+// match the active colour scheme
+const PALETTE: [&str; 16] = [
+  "base00", "base08", "base0B", "base0A", "base0D", "base0E", "base0C",
+  "base05", "base03", "base08", "base0B", "base0A", "base0D", "base0E",
+  "base0C", "base07",
+];
+
+#[derive(Clone, Copy)]
+enum Mode {
+  Render,
+  Strip,
+  Pass,
+}
+
+impl Mode {
+  fn from_environment() -> Self {
+    match ENVIRONMENT.ansi_escapes.as_deref().map(str::to_lowercase).as_deref()
+    {
+      Some("strip") => Self::Strip,
+      Some("pass") => Self::Pass,
+      _ => Self::Render,
+    }
+  }
+}
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Default, PartialEq, Eq)]
+struct Style {
+  bold:       bool,
+  dim:        bool,
+  italic:     bool,
+  underline:  bool,
+  foreground: Option<String>,
+  background: Option<String>,
+}
+
+impl Style {
+  fn css(&self) -> String {
+    let mut css = String::new();
+
+    if let Some(foreground) = &self.foreground {
+      let _ = write!(&mut css, "color: {foreground};");
+    }
+
+    if let Some(background) = &self.background {
+      let _ = write!(&mut css, "background-color: {background};");
+    }
+
+    if self.bold {
+      css.push_str("font-weight: bold;");
+    }
+
+    if self.dim {
+      css.push_str("opacity: 0.7;");
+    }
+
+    if self.italic {
+      css.push_str("font-style: italic;");
+    }
+
+    if self.underline {
+      css.push_str("text-decoration: underline;");
+    }
+
+    css
+  }
+
+  fn apply(&mut self, parameters: &str) {
+    let mut codes =
+      parameters.split([';', ':']).map(|code| code.parse::<u8>().unwrap_or(0));
+
+    while let Some(code) = codes.next() {
+      match code {
+        0 => *self = Self::default(),
+        1 => self.bold = true,
+        2 => self.dim = true,
+        3 => self.italic = true,
+        4 => self.underline = true,
+        22 => {
+          self.bold = false;
+          self.dim = false;
+        }
+        23 => self.italic = false,
+        24 => self.underline = false,
+        30..=37 => self.foreground = Some(indexed(code - 30)),
+        90..=97 => self.foreground = Some(indexed(code - 90 + 8)),
+        40..=47 => self.background = Some(indexed(code - 40)),
+        100..=107 => self.background = Some(indexed(code - 100 + 8)),
+        38 => self.foreground = extended(&mut codes),
+        48 => self.background = extended(&mut codes),
+        39 => self.foreground = None,
+        49 => self.background = None,
+        _ => {}
+      }
+    }
+  }
+}
+
+fn indexed(index: u8) -> String {
+  match index {
+    0..=15 => format!("var(--{})", PALETTE[usize::from(index)]),
+    16..=231 => {
+      let level = |value: u8| if value == 0 { 0 } else { value * 40 + 55 };
+      let index = index - 16;
+
+      format!(
+        "#{:02x}{:02x}{:02x}",
+        level(index / 36),
+        level(index / 6 % 6),
+        level(index % 6)
+      )
+    }
+    _ => {
+      let grey = (index - 232) * 10 + 8;
+
+      format!("#{grey:02x}{grey:02x}{grey:02x}")
+    }
+  }
+}
+
+// `38;5;n` selects from the 256 colour palette, and `38;2;r;g;b` is truecolour
+fn extended(codes: &mut impl Iterator<Item = u8>) -> Option<String> {
+  match codes.next()? {
+    5 => Some(indexed(codes.next()?)),
+    2 => Some(format!(
+      "#{:02x}{:02x}{:02x}",
+      codes.next()?,
+      codes.next()?,
+      codes.next()?
+    )),
+    _ => None,
+  }
+}
+
+pub fn contains_escapes(text: &str) -> bool { text.contains('\x1b') }
+
+pub fn render(text: &str) -> String {
+  let mode = Mode::from_environment();
+
+  if matches!(mode, Mode::Pass) {
+    return escape::text(text);
+  }
+
+  let mut html = String::with_capacity(text.len());
+  let mut style = Style::default();
+  let mut open = Style::default();
+  let mut characters = text.char_indices().peekable();
+  let mut plain_start = 0;
+  let mut flush = |html: &mut String, plain: &str, style: &Style| {
+    if plain.is_empty() {
+      return;
+    }
+
+    if matches!(mode, Mode::Render) && *style != open {
+      if open != Style::default() {
+        html.push_str("</span>");
+      }
+
+      if *style != Style::default() {
+        let _ = write!(html, "<span style=\"{}\">", style.css());
+      }
+
+      open = style.clone();
+    }
+
+    html.push_str(&escape::text(plain));
+  };
+
+  while let Some((index, character)) = characters.next() {
+    if character != '\x1b' {
+      continue;
+    }
+
+    flush(&mut html, &text[plain_start..index], &style);
+
+    match characters.peek().map(|(_, character)| *character) {
+      // Control Sequence Introducer: parameters up to a final byte
+      Some('[') => {
+        characters.next();
+
+        let parameters_start = index + 2;
+        let mut end = text.len();
+        let mut last = None;
+
+        for (index, character) in characters.by_ref() {
+          if ('\x40'..='\x7e').contains(&character) {
+            end = index;
+            last = Some(character);
+
+            break;
+          }
+        }
+
+        if last == Some('m') {
+          style.apply(&text[parameters_start..end]);
+        }
+
+        plain_start = end + last.map_or(0, char::len_utf8);
+      }
+      // Operating System Command: terminated by BEL or ESC \
+      Some(']') => {
+        let mut end = text.len();
+
+        while let Some((index, character)) = characters.next() {
+          if character == '\x07' {
+            end = index + 1;
+
+            break;
+          }
+
+          if character == '\x1b'
+            && characters.peek().is_some_and(|(_, next)| *next == '\\')
+          {
+            end = index + 2;
+
+            characters.next();
+
+            break;
+          }
+        }
+
+        plain_start = end;
+      }
+      Some(_) => {
+        let (index, character) = characters.next().unwrap_or_default();
+
+        plain_start = index + character.len_utf8();
+      }
+      None => plain_start = text.len(),
+    }
+  }
+
+  flush(&mut html, &text[plain_start..], &style);
+
+  if matches!(mode, Mode::Render) && open != Style::default() {
+    html.push_str("</span>");
+  }
+
+  html
+}
@@ -0,0 +1,95 @@
+// Every piece of capsule-controlled content must pass through one of these
+// before it is written into HTML
+
+pub fn text(input: &str) -> String {
+  let mut escaped = String::with_capacity(input.len());
+
+  for character in input.chars() {
+    match character {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      _ => escaped.push(character),
+    }
+  }
+
+  escaped
+}
+
+pub fn attribute(input: &str) -> String {
+  let mut escaped = String::with_capacity(input.len());
+
+  for character in input.chars() {
+    match character {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&#39;"),
+      _ => escaped.push(character),
+    }
+  }
+
+  escaped
+}
+
+// Browsers ignore ASCII whitespace and control characters when reading a URL
+// scheme, so `java\tscript:` is just as dangerous as `javascript:`
+fn is_dangerous(href: &str) -> bool {
+  let scheme = href
+    .chars()
+    .filter(|character| {
+      !character.is_ascii_whitespace() && !character.is_control()
+    })
+    .take_while(|character| *character != ':')
+    .collect::<String>()
+    .to_ascii_lowercase();
+
+  href.contains(':')
+    && ["javascript", "vbscript", "data"].contains(&scheme.as_str())
+}
+
+pub fn href(input: &str) -> String {
+  if is_dangerous(input) { "#".to_string() } else { attribute(input) }
+}
+
+#[cfg(test)]
+mod tests {
+  #[test]
+  fn escapes_text() {
+    assert_eq!(
+      super::text("<script>alert('x') & \"y\"</script>"),
+      "&lt;script&gt;alert('x') &amp; \"y\"&lt;/script&gt;"
+    );
+  }
+
+  #[test]
+  fn escapes_attributes() {
+    assert_eq!(
+      super::attribute("\" onmouseover='alert(1)' <"),
+      "&quot; onmouseover=&#39;alert(1)&#39; &lt;"
+    );
+  }
+
+  #[test]
+  fn neutralises_dangerous_hrefs() {
+    for href in [
+      "javascript:alert(1)",
+      "JavaScript:alert(1)",
+      " java\tscript:alert(1)",
+      "vbscript:msgbox(1)",
+      "data:text/html,<script>alert(1)</script>",
+    ] {
+      assert_eq!(super::href(href), "#", "{href}");
+    }
+
+    assert_eq!(
+      super::href("gemini://fuwn.me/?a=1&b=2"),
+      "gemini://fuwn.me/?a=1&amp;b=2"
+    );
+    assert_eq!(
+      super::href("/proxy/fuwn.me/javascript"),
+      "/proxy/fuwn.me/javascript"
+    );
+  }
+}
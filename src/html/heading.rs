@@ -0,0 +1,122 @@
+use {
+  super::escape,
+  crate::{environment::ENVIRONMENT, url::matches_pattern},
+  std::{collections::HashSet, fmt::Write},
+  url::Url,
+};
+
+#[derive(Default)]
+pub struct Headings {
+  seen:    HashSet<String>,
+  entries: Vec<(usize, String, String)>,
+}
+
+fn slug(text: &str) -> String {
+  let mut slug = String::with_capacity(text.len());
+
+  for character in text.chars() {
+    if character.is_alphanumeric() {
+      slug.extend(character.to_lowercase());
+    } else if !slug.is_empty() && !slug.ends_with('-') {
+      slug.push('-');
+    }
+  }
+
+  let slug = slug.trim_end_matches('-');
+
+  if slug.is_empty() { "section".to_string() } else { slug.to_string() }
+}
+
+impl Headings {
+  // Identical headings are numbered in order of appearance, so an id only
+  // changes when a heading with the same text is added above it
+  fn insert(&mut self, level: usize, text: &str) -> String {
+    let slug = slug(text);
+    let mut id = slug.clone();
+    let mut count = 1;
+
+    while self.seen.contains(&id) {
+      count += 1;
+      id = format!("{slug}-{count}");
+    }
+
+    self.seen.insert(id.clone());
+    self.entries.push((level, id.clone(), text.to_string()));
+
+    id
+  }
+
+  pub fn element(&mut self, level: usize, text: &str, content: &str) -> String {
+    let id = escape::attribute(&self.insert(level, text));
+    let tag = match level {
+      1 => "h1",
+      2 => "h2",
+      3 => "h3",
+      _ => "p",
+    };
+    let mut html = format!("<{tag} id=\"{id}\">{content}");
+
+    if ENVIRONMENT.heading_links {
+      let _ = write!(
+        &mut html,
+        " <a class=\"heading-link\" href=\"#{id}\" aria-label=\"Link to this \
+         section\">#</a>"
+      );
+    }
+
+    let _ = write!(&mut html, "</{tag}>");
+
+    html
+  }
+
+  pub fn table_of_contents(&self, url: &Url) -> Option<String> {
+    if self.entries.len() < ENVIRONMENT.table_of_contents_minimum
+      || !ENVIRONMENT.table_of_contents.iter().any(|pattern| {
+        matches_pattern(pattern, url.path())
+          || matches_pattern(pattern, url.as_str())
+      })
+    {
+      return None;
+    }
+
+    let mut html = String::from(
+      "<nav class=\"table-of-contents\"><details \
+       open><summary>Contents</summary>",
+    );
+    let mut depth = 0;
+    let base = self.entries.iter().map(|(level, ..)| *level).min().unwrap_or(1);
+
+    for (level, id, text) in &self.entries {
+      let level = level - base + 1;
+
+      if level > depth {
+        for _ in depth..level {
+          html.push_str("<ul><li>");
+        }
+      } else {
+        for _ in level..depth {
+          html.push_str("</li></ul>");
+        }
+
+        html.push_str("</li><li>");
+      }
+
+      depth = level;
+
+      let _ = write!(
+        &mut html,
+        "<a href=\"#{}\">{}</a>",
+        escape::attribute(id),
+        escape::text(text)
+      );
+    }
+
+    for _ in 0..depth {
+      html.push_str("</li></ul>");
+    }
+
+    html.push_str("</details></nav>");
+
+    Some(html)
+  }
+}
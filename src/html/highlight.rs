@@ -0,0 +1,39 @@
+use {
+  crate::environment::ENVIRONMENT,
+  std::sync::LazyLock,
+  syntect::{
+    html::{ClassStyle, ClassedHTMLGenerator},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+  },
+};
+
+static SYNTAXES: LazyLock<SyntaxSet> =
+  LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+// The first word of the alt text is taken as a language name or file
+// extension, as in "```rust" or "```sh install.sh"
+pub fn highlight(text: &str, alt_text: Option<&str>) -> Option<String> {
+  if !ENVIRONMENT.syntax_highlighting {
+    return None;
+  }
+
+  let token = alt_text?.split_whitespace().next()?;
+  let syntax = SYNTAXES.find_syntax_by_token(token)?;
+
+  if syntax.name == "Plain Text" {
+    return None;
+  }
+
+  let mut generator = ClassedHTMLGenerator::new_with_class_style(
+    syntax,
+    &SYNTAXES,
+    ClassStyle::SpacedPrefixed { prefix: "syntax-" },
+  );
+
+  for line in LinesWithEndings::from(text) {
+    generator.parse_html_for_line_which_includes_newline(line).ok()?;
+  }
+
+  Some(generator.finalize())
+}
@@ -0,0 +1,245 @@
+use {
+  super::escape,
+  crate::{environment::ENVIRONMENT, url::matches_pattern},
+  std::fmt::Write,
+  url::Url,
+};
+
+#[derive(Clone, Copy)]
+pub enum Markup {
+  Strict,
+  Limited,
+  Full,
+}
+
+impl Markup {
+  fn from_name(name: &str) -> Option<Self> {
+    match name.trim().to_lowercase().as_str() {
+      "strict" => Some(Self::Strict),
+      "limited" => Some(Self::Limited),
+      "full" => Some(Self::Full),
+      _ => None,
+    }
+  }
+
+  pub fn for_url(url: &Url) -> Self {
+    ENVIRONMENT
+      .inline_markup_paths
+      .iter()
+      .find(|(pattern, _)| {
+        matches_pattern(pattern, url.path())
+          || matches_pattern(pattern, url.as_str())
+      })
+      .map(|(_, markup)| markup)
+      .or(ENVIRONMENT.inline_markup.as_ref())
+      .and_then(|markup| Self::from_name(markup))
+      .unwrap_or(Self::Full)
+  }
+}
+
+pub fn render(text: &str, markup: Markup) -> String {
+  match markup {
+    Markup::Strict => escape::text(text),
+    Markup::Limited => limited(text),
+    Markup::Full => full(text),
+  }
+}
+
+fn full(text: &str) -> String {
+  let is_ordered_list = text.starts_with(|c: char| c.is_ascii_digit())
+    && text.get(1..3) == Some(". ");
+
+  if is_ordered_list {
+    return escape::text(text);
+  }
+
+  let mut options = comrak::Options::default();
+
+  options.extension.strikethrough = true;
+  options.extension.table = true;
+  options.extension.footnotes = true;
+
+  let html = comrak::markdown_to_html(text, &options);
+
+  // A line which only defines a footnote has nothing to render on its own, so
+  // it is kept as written rather than dropped
+  if html.trim().is_empty() {
+    return escape::text(text);
+  }
+
+  // Text lines are already wrapped by the caller, so only the paragraph which
+  // wraps a plain line is unwrapped
+  html
+    .trim_end()
+    .strip_prefix("<p>")
+    .and_then(|html| html.strip_suffix("</p>"))
+    .filter(|html| !html.contains("<p>"))
+    .map_or_else(|| html.clone(), ToString::to_string)
+}
+
+fn is_word(character: Option<char>) -> bool {
+  character.is_some_and(char::is_alphanumeric)
+}
+
+fn autolink_length(text: &str) -> Option<usize> {
+  ["gemini://", "https://", "http://", "gopher://"]
+    .iter()
+    .any(|scheme| text.starts_with(scheme))
+    .then(|| {
+      text
+        .find(|c: char| c.is_whitespace() || c == '<' || c == '>')
+        .unwrap_or(text.len())
+    })
+    .map(|length| {
+      // Trailing punctuation is far more likely to end the sentence than the
+      // URL
+      text[..length].trim_end_matches(['.', ',', ':', ';', '!', '?', ')']).len()
+    })
+}
+
+// Code spans, emphasis, and autolinks, and nothing which could change the
+// block structure of the line
+fn limited(text: &str) -> String {
+  let mut html = String::with_capacity(text.len());
+  let mut rest = text;
+  let mut previous = None;
+
+  while let Some(character) = rest.chars().next() {
+    if character == '`' {
+      if let Some(end) = rest[1..].find('`') {
+        html.push_str("<code>");
+        html.push_str(&escape::text(&rest[1..=end]));
+        html.push_str("</code>");
+
+        rest = &rest[end + 2..];
+        previous = Some('`');
+
+        continue;
+      }
+    }
+
+    if character == '<' {
+      if let Some(end) = rest.find('>') {
+        let target = &rest[1..end];
+
+        if autolink_length(target) == Some(target.len()) {
+          let _ = write!(
+            &mut html,
+            "<a href=\"{}\">{}</a>",
+            escape::href(target),
+            escape::text(target)
+          );
+
+          rest = &rest[end + 1..];
+          previous = Some('>');
+
+          continue;
+        }
+      }
+    }
+
+    if !is_word(previous) {
+      if let Some(length) = autolink_length(rest) {
+        let target = &rest[..length];
+        let _ = write!(
+          &mut html,
+          "<a href=\"{}\">{}</a>",
+          escape::href(target),
+          escape::text(target)
+        );
+
+        rest = &rest[length..];
+        previous = target.chars().last();
+
+        continue;
+      }
+    }
+
+    if (character == '*' || character == '_') && !is_word(previous) {
+      let delimiter =
+        if rest[1..].starts_with(character) { &rest[..2] } else { &rest[..1] };
+      let inner_start = delimiter.len();
+
+      if rest[inner_start..].starts_with(|c: char| !c.is_whitespace()) {
+        let closing =
+          rest[inner_start..].match_indices(delimiter).find(|(index, _)| {
+            let end = inner_start + index;
+
+            !rest[..end].ends_with(char::is_whitespace)
+              && !is_word(rest[end + delimiter.len()..].chars().next())
+          });
+
+        if let Some((index, _)) = closing {
+          let end = inner_start + index;
+          let tag = if delimiter.len() == 2 { "strong" } else { "em" };
+          let _ = write!(
+            &mut html,
+            "<{tag}>{}</{tag}>",
+            limited(&rest[inner_start..end])
+          );
+
+          rest = &rest[end + delimiter.len()..];
+          previous = Some(character);
+
+          continue;
+        }
+      }
+    }
+
+    html.push_str(&escape::text(&rest[..character.len_utf8()]));
+
+    rest = &rest[character.len_utf8()..];
+    previous = Some(character);
+  }
+
+  html
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{full, limited};
+
+  #[test]
+  fn renders_code_spans() {
+    assert_eq!(limited("a `<b>` c"), "a <code>&lt;b&gt;</code> c");
+    assert_eq!(limited("`é`"), "<code>é</code>");
+    assert_eq!(limited("unclosed `"), "unclosed `");
+    assert_eq!(limited("``"), "<code></code>");
+  }
+
+  #[test]
+  fn renders_emphasis() {
+    assert_eq!(limited("*a* and _b_"), "<em>a</em> and <em>b</em>");
+    assert_eq!(limited("**a `b`**"), "<strong>a <code>b</code></strong>");
+    assert_eq!(limited("snake_case_name"), "snake_case_name");
+    assert_eq!(limited("* a*"), "* a*");
+    assert_eq!(limited("*é*"), "<em>é</em>");
+  }
+
+  #[test]
+  fn leaves_trailing_delimiters() {
+    for text in ["*", "_", "**", "a *", "é*", "`"] {
+      assert_eq!(limited(text), text);
+    }
+  }
+
+  #[test]
+  fn renders_autolinks() {
+    assert_eq!(
+      limited("see gemini://a.b/c."),
+      "see <a href=\"gemini://a.b/c\">gemini://a.b/c</a>."
+    );
+    assert_eq!(
+      limited("<https://a.b>"),
+      "<a href=\"https://a.b\">https://a.b</a>"
+    );
+    assert_eq!(limited("<b>"), "&lt;b&gt;");
+    assert_eq!(limited("xhttps://a.b"), "xhttps://a.b");
+  }
+
+  #[test]
+  fn renders_extensions() {
+    assert_eq!(full("~~gone~~"), "<del>gone</del>");
+    assert_eq!(full("[^1]: a note"), "[^1]: a note");
+  }
+}
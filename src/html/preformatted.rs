@@ -0,0 +1,92 @@
+use {
+  super::{ansi, escape, highlight},
+  crate::environment::ENVIRONMENT,
+  std::fmt::Write,
+};
+
+const ART_KEYWORDS: [&str; 8] =
+  ["art", "ascii", "banner", "logo", "drawing", "diagram", "figlet", "image"];
+
+// Blocks which are mostly symbols are far more likely to be drawings than
+// prose or code
+fn looks_like_art(text: &str, alt_text: Option<&str>) -> bool {
+  if alt_text.is_some_and(|alt_text| {
+    alt_text.split(|c: char| !c.is_alphanumeric()).any(|word| {
+      ART_KEYWORDS.iter().any(|keyword| word.eq_ignore_ascii_case(keyword))
+    })
+  }) {
+    return true;
+  }
+
+  let (symbols, total) = text
+    .chars()
+    .filter(|character| !character.is_whitespace())
+    .fold((0, 0), |(symbols, total), character| {
+      (symbols + usize::from(!character.is_alphanumeric()), total + 1)
+    });
+
+  total >= 16 && symbols * 10 >= total * 6
+}
+
+pub fn render(text: &str, alt_text: Option<&str>) -> String {
+  let text = text.strip_suffix('\n').unwrap_or(text);
+  let alt_text = alt_text.map(str::trim).filter(|alt| !alt.is_empty());
+  let highlighted = if ansi::contains_escapes(text) {
+    None
+  } else {
+    highlight::highlight(text, alt_text)
+  };
+  let is_art = ENVIRONMENT.ascii_art_detection
+    && highlighted.is_none()
+    && looks_like_art(text, alt_text);
+  let content = highlighted.unwrap_or_else(|| {
+    if ansi::contains_escapes(text) {
+      ansi::render(text)
+    } else {
+      escape::text(text)
+    }
+  });
+  let figure = ENVIRONMENT.preformatted_alt_text.as_deref() == Some("figure");
+  // Drawings are announced by their label alone so that screen readers can
+  // skip the glyphs. Without alt text to offer, they are still named.
+  let label = match ENVIRONMENT.preformatted_alt_text.as_deref() {
+    Some("none") if is_art => Some("ASCII art"),
+    Some("figure" | "none") if !is_art => None,
+    _ if is_art => Some(alt_text.unwrap_or("ASCII art")),
+    _ => alt_text,
+  };
+  let mut html = String::new();
+
+  if figure && alt_text.is_some() {
+    html.push_str("<figure>");
+  }
+
+  html.push_str("<pre");
+
+  // `aria-label` is ignored on a bare `<pre>`, so labelled blocks are given a
+  // role which permits naming
+  if is_art {
+    html.push_str(" role=\"img\"");
+  } else if label.is_some() {
+    html.push_str(" role=\"figure\"");
+  }
+
+  if let Some(label) = label {
+    let _ = write!(&mut html, " aria-label=\"{}\"", escape::attribute(label));
+  }
+
+  let _ = write!(&mut html, ">{content}</pre>");
+
+  // A drawing is already named by its label, so its caption is hidden from
+  // screen readers to avoid announcing the alt text twice
+  if let Some(alt_text) = alt_text.filter(|_| figure) {
+    let _ = write!(
+      &mut html,
+      "<figcaption{}>{}</figcaption></figure>",
+      if is_art { " aria-hidden=\"true\"" } else { "" },
+      escape::text(alt_text)
+    );
+  }
+
+  html
+}
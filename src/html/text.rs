@@ -0,0 +1,55 @@
+use {super::escape, std::fmt::Write};
+
+pub fn plain(content: &str) -> String {
+  format!(
+    "<pre>{}</pre>",
+    escape::text(content.strip_suffix('\n').unwrap_or(content))
+  )
+}
+
+pub fn markdown(content: &str) -> (String, String) {
+  let mut options = comrak::Options::default();
+
+  options.extension.strikethrough = true;
+  options.extension.table = true;
+  options.extension.autolink = true;
+  options.extension.tasklist = true;
+  options.extension.footnotes = true;
+  // Raw HTML and dangerous link schemes are stripped unless this is set
+  options.render.unsafe_ = false;
+
+  let title = content
+    .lines()
+    .find_map(|line| line.strip_prefix("# "))
+    .map_or_else(String::new, |title| escape::text(title.trim()));
+
+  (title, comrak::markdown_to_html(content, &options))
+}
+
+pub fn csv(content: &str, delimiter: u8) -> String {
+  let mut reader = csv::ReaderBuilder::new()
+    .delimiter(delimiter)
+    .has_headers(false)
+    .flexible(true)
+    .from_reader(content.as_bytes());
+  let mut html = String::from("<table>");
+
+  for (index, record) in reader.records().enumerate() {
+    let Ok(record) = record else {
+      return plain(content);
+    };
+    let cell = if index == 0 { "th" } else { "td" };
+
+    html.push_str("<tr>");
+
+    for field in &record {
+      let _ = write!(&mut html, "<{cell}>{}</{cell}>", escape::text(field));
+    }
+
+    html.push_str("</tr>");
+  }
+
+  html.push_str("</table>");
+
+  html
+}
@@ -1,5 +1,6 @@
 use {
   crate::{environment::ENVIRONMENT, url::from_path},
+  futures_util::StreamExt,
   tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
     net::TcpListener,
@@ -51,28 +52,32 @@ async fn handle(
   let path = parse_request(&request_line)?;
   let mut configuration = crate::response::configuration::Configuration::new();
   let url = from_path(&path, false, &mut configuration)?;
-  let mut response = germ::request::request(&url).await?;
+  let (response, redirects, body) = crate::gemini::request_following(
+    &url,
+    None,
+    false,
+    crate::gemini::Response::is_streamable,
+  )
+  .await?;
 
-  if *response.status() == germ::request::Status::PermanentRedirect
-    || *response.status() == germ::request::Status::TemporaryRedirect
+  if let Some(mut body) = body {
+    while let Some(chunk) = body.next().await {
+      writer.write_all(&chunk?).await?;
+    }
+  } else if ENVIRONMENT.http09_text
+    && *response.status() == germ::request::Status::Success
+    && response.mime() == "text/gemini"
   {
-    let redirect = if response.meta().starts_with('/') {
-      format!(
-        "gemini://{}{}",
-        url.domain().unwrap_or_default(),
-        response.meta()
-      )
-    } else {
-      response.meta().to_string()
-    };
+    let url = redirects.last().map_or(&url, crate::gemini::Redirect::target);
 
-    response = germ::request::request(&url::Url::parse(&redirect)?).await?;
-  }
-
-  if response.meta().starts_with("image/") {
-    if let Some(bytes) = response.content_bytes() {
-      writer.write_all(bytes).await?;
-    }
+    writer
+      .write_all(
+        crate::text::from_gemtext(&response.content().unwrap_or_default(), url)
+          .as_bytes(),
+      )
+      .await?;
+  } else if response.is_binary() {
+    writer.write_all(response.content_bytes().unwrap_or_default()).await?;
   } else if let Some(content) = response.content() {
     writer.write_all(content.as_bytes()).await?;
   }
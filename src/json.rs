@@ -0,0 +1,141 @@
+use {
+  crate::{
+    gemini::{Redirect, Response},
+    response::configuration::Configuration,
+  },
+  serde::Serialize,
+  std::time::{Duration, Instant},
+  url::Url,
+};
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Node<'a> {
+  Text {
+    text: &'a str,
+  },
+  Link {
+    to:      &'a str,
+    text:    Option<&'a str>,
+    url:     Option<String>,
+    proxied: Option<String>,
+  },
+  Heading {
+    level: usize,
+    text:  &'a str,
+  },
+  List {
+    items: &'a [String],
+  },
+  Blockquote {
+    text: &'a str,
+  },
+  Preformatted {
+    alt_text: Option<&'a str>,
+    text:     &'a str,
+  },
+  Whitespace,
+}
+
+impl<'a> Node<'a> {
+  fn from_gemtext(
+    node: &'a germ::ast::Node,
+    url: &Url,
+    configuration: &Configuration,
+  ) -> Self {
+    match node {
+      germ::ast::Node::Text(text) => Self::Text { text },
+      germ::ast::Node::Link { to, text } => Self::Link {
+        to,
+        text: text.as_deref(),
+        url: url.join(to).ok().map(String::from),
+        proxied: crate::html::resolve_link(url, configuration, to),
+      },
+      germ::ast::Node::Heading { level, text } =>
+        Self::Heading { level: *level, text },
+      germ::ast::Node::List(items) => Self::List { items },
+      germ::ast::Node::Blockquote(text) => Self::Blockquote { text },
+      germ::ast::Node::PreformattedText { alt_text, text } =>
+        Self::Preformatted { alt_text: alt_text.as_deref(), text },
+      germ::ast::Node::Whitespace => Self::Whitespace,
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct RedirectEntry<'a> {
+  target:    &'a str,
+  permanent: bool,
+}
+
+#[derive(Serialize)]
+struct Timing {
+  response_milliseconds: f64,
+  parse_milliseconds:    f64,
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+  url:       &'a str,
+  status:    i32,
+  meta:      &'a str,
+  timing:    Timing,
+  redirects: Vec<RedirectEntry<'a>>,
+  nodes:     Option<Vec<Node<'a>>>,
+  content:   Option<String>,
+}
+
+#[derive(Serialize)]
+struct Failure<'a> {
+  error: &'a str,
+}
+
+pub fn from_response(
+  response: &Response,
+  redirects: &[Redirect],
+  url: &Url,
+  configuration: &Configuration,
+  response_time: Duration,
+) -> String {
+  let timer = Instant::now();
+  let is_gemtext = *response.status() == germ::request::Status::Success
+    && response.mime() == "text/gemini";
+  // Only gemtext has nodes; other text is passed along as it is
+  let (ast, content) = match response.content() {
+    Some(content) if is_gemtext =>
+      (Some(germ::ast::Ast::from_string(content)), None),
+    content if !response.is_binary() => (None, content),
+    _ => (None, None),
+  };
+  let nodes = ast.as_ref().map(|ast| {
+    ast
+      .inner()
+      .iter()
+      .map(|node| Node::from_gemtext(node, url, configuration))
+      .collect()
+  });
+  let document = Document {
+    url: url.as_str(),
+    status: i32::from(*response.status()),
+    meta: response.meta(),
+    timing: Timing {
+      response_milliseconds: response_time.as_nanos() as f64 / 1_000_000.0,
+      parse_milliseconds:    timer.elapsed().as_nanos() as f64 / 1_000_000.0,
+    },
+    redirects: redirects
+      .iter()
+      .map(|redirect| RedirectEntry {
+        target:    redirect.target().as_str(),
+        permanent: redirect.is_permanent(),
+      })
+      .collect(),
+    nodes,
+    content,
+  };
+
+  serde_json::to_string(&document).unwrap_or_default()
+}
+
+pub fn error(message: &str) -> String {
+  serde_json::to_string(&Failure { error: message }).unwrap_or_default()
+}
@@ -11,14 +11,22 @@
 #![allow(clippy::cast_precision_loss)]
 
 mod environment;
+mod gemini;
 mod html;
 mod http09;
+mod json;
+mod markdown;
 mod response;
+mod text;
 mod url;
 
 #[macro_use] extern crate log;
 
-use {actix_web::web, response::default, std::env::var};
+use {
+  actix_web::web,
+  response::{default, purge},
+  std::env::var,
+};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -35,7 +43,26 @@ async fn main() -> std::io::Result<()> {
   }
 
   actix_web::HttpServer::new(move || {
-    actix_web::App::new()
+    let mut app = actix_web::App::new();
+
+    if environment::ENVIRONMENT.cache_purge_token.is_some() {
+      app = app.service(
+        web::scope("/purge")
+          .guard(actix_web::guard::Post())
+          .default_service(web::to(purge)),
+      );
+    }
+
+    if environment::ENVIRONMENT.ephemeral_identities {
+      app = app.service(
+        web::scope("/identity")
+          .guard(actix_web::guard::Post())
+          .route("/create", web::to(response::identity::create))
+          .route("/delete", web::to(response::identity::delete)),
+      );
+    }
+
+    app
       .default_service(web::get().to(default))
       .wrap(actix_web::middleware::Logger::default())
   })
@@ -0,0 +1,119 @@
+use {
+  crate::environment::ENVIRONMENT, germ::ast::Node, std::fmt::Write, url::Url,
+};
+
+// Gemtext has no inline markup, so anything CommonMark or GFM would interpret
+// is escaped
+fn escape(text: &str) -> String {
+  let text = text.trim_start();
+  let mut escaped = String::with_capacity(text.len());
+
+  for character in text.chars() {
+    if matches!(
+      character,
+      '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '&' | '|' | '~'
+    ) {
+      escaped.push('\\');
+    }
+
+    escaped.push(character);
+  }
+
+  // Block markers are only significant at the start of a line
+  if escaped.starts_with(['-', '+', '=']) {
+    escaped.insert(0, '\\');
+  } else if let Some(digits) =
+    escaped.find(|character: char| !character.is_ascii_digit())
+  {
+    if digits > 0 && escaped[digits..].starts_with(['.', ')']) {
+      escaped.insert(digits, '\\');
+    }
+  }
+
+  escaped
+}
+
+fn destination(target: &str) -> String {
+  if target.contains([' ', '(', ')', '<', '>']) {
+    format!("<{}>", target.replace('<', "%3C").replace('>', "%3E"))
+  } else {
+    target.to_string()
+  }
+}
+
+fn fence(text: &str, alt_text: Option<&str>) -> String {
+  let alt_text = alt_text.map(str::trim).unwrap_or_default();
+  // Backtick fences cannot carry backticks in their info string
+  let marker = if alt_text.contains('`') { '~' } else { '`' };
+  let mut longest = 0;
+  let mut run = 0;
+
+  for character in text.chars() {
+    run = if character == marker { run + 1 } else { 0 };
+    longest = longest.max(run);
+  }
+
+  let fence = marker.to_string().repeat((longest + 1).max(3));
+
+  format!(
+    "{fence}{alt_text}\n{}\n{fence}",
+    text.strip_suffix('\n').unwrap_or(text)
+  )
+}
+
+pub fn from_gemtext(content: &str, url: &Url) -> String {
+  let ast = germ::ast::Ast::from_string(content);
+  let reference_links = ENVIRONMENT
+    .markdown_links
+    .as_deref()
+    .is_some_and(|links| links.eq_ignore_ascii_case("reference"));
+  let mut blocks = Vec::new();
+  let mut references = String::new();
+  let mut link_count = 0;
+
+  for node in ast.inner() {
+    blocks.push(match node {
+      Node::Text(text) => escape(text),
+      Node::Heading { level, text } =>
+        format!("{} {}", "#".repeat((*level).clamp(1, 6)), escape(text)),
+      Node::List(items) => items
+        .iter()
+        .map(|item| format!("- {}", escape(item)))
+        .collect::<Vec<_>>()
+        .join("\n"),
+      Node::Blockquote(text) => format!("> {}", escape(text)),
+      Node::PreformattedText { alt_text, text } =>
+        fence(text, alt_text.as_deref()),
+      Node::Link { to, text } => {
+        let target = url.join(to).map_or_else(|_| to.clone(), String::from);
+        let text = escape(text.as_deref().unwrap_or(&target));
+
+        if reference_links {
+          link_count += 1;
+
+          let _ = writeln!(
+            &mut references,
+            "[{link_count}]: {}",
+            destination(&target)
+          );
+
+          format!("[{text}][{link_count}]")
+        } else {
+          format!("[{text}]({})", destination(&target))
+        }
+      }
+      Node::Whitespace => continue,
+    });
+  }
+
+  let mut markdown = blocks.join("\n\n");
+
+  if !references.is_empty() {
+    markdown.push_str("\n\n");
+    markdown.push_str(&references);
+  } else if !markdown.is_empty() {
+    markdown.push('\n');
+  }
+
+  markdown
+}
@@ -1,15 +1,43 @@
 pub mod configuration;
+pub mod identity;
+mod range;
+mod status;
 
 use {
   crate::{
     environment::ENVIRONMENT,
+    gemini::cache::CACHE,
+    html::escape,
     url::{from_path as url_from_path, matches_pattern},
   },
-  actix_web::{Error, HttpResponse},
+  actix_web::{
+    Error, HttpRequest, HttpResponse, HttpResponseBuilder,
+    http::{
+      StatusCode,
+      header::{
+        ACCEPT, ACCEPT_RANGES, ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_RANGE,
+        CONTENT_SECURITY_POLICY, ContentDisposition, DispositionParam,
+        DispositionType, ETAG, HeaderValue, ORIGIN, RANGE, USER_AGENT, VARY,
+        X_CONTENT_TYPE_OPTIONS,
+      },
+    },
+  },
   std::{fmt::Write, time::Instant},
 };
 
 const CSS: &str = include_str!("../default.css");
+// Capsule content which is served verbatim must not be able to run script on
+// the proxy's origin
+const SANDBOX: &str = "sandbox";
+const TERMINAL_CLIENTS: [&str; 4] = ["curl/", "wget/", "httpie/", "xh/"];
+// In order of precedence when the client has no preference between them
+const FORMS: [&str; 5] = [
+  "text/html",
+  "text/gemini",
+  "text/markdown",
+  "text/plain",
+  "application/json",
+];
 
 #[derive(serde::Deserialize)]
 pub struct InputSubmission {
@@ -17,28 +45,255 @@ pub struct InputSubmission {
   target: Option<String>,
 }
 
-fn html_escape(input: &str) -> String {
-  input
-    .replace('&', "&amp;")
-    .replace('"', "&quot;")
-    .replace('<', "&lt;")
-    .replace('>', "&gt;")
+fn redirect_notice(redirects: &[crate::gemini::Redirect]) -> String {
+  if redirects.is_empty() {
+    return String::new();
+  }
+
+  let mut notice = String::from("<blockquote>This page");
+
+  for (index, redirect) in redirects.iter().enumerate() {
+    let _ = write!(
+      &mut notice,
+      "{} {} redirects to <a href=\"{}\">{2}</a>",
+      if index == 0 { "" } else { ", which" },
+      if redirect.is_permanent() { "permanently" } else { "temporarily" },
+      escape::href(redirect.target().as_str()),
+    );
+  }
+
+  notice.push_str(".</blockquote>");
+
+  notice
 }
 
-#[allow(clippy::future_not_send, clippy::too_many_lines)]
+fn content_disposition(url: &url::Url) -> ContentDisposition {
+  let filename = url
+    .path_segments()
+    .and_then(Iterator::last)
+    .filter(|segment| !segment.is_empty())
+    .map_or_else(
+      || url.host_str().unwrap_or("download").to_string(),
+      |segment| {
+        percent_encoding::percent_decode_str(segment)
+          .decode_utf8_lossy()
+          .to_string()
+      },
+    );
+
+  ContentDisposition {
+    disposition: DispositionType::Inline,
+    parameters:  vec![DispositionParam::Filename(filename)],
+  }
+}
+
+// Any origin is answered with a wildcard, and listed origins are echoed back.
+// Credentials are never allowed.
+fn cross_origin<'a>(
+  http_request: &HttpRequest,
+  response_builder: &'a mut HttpResponseBuilder,
+) -> &'a mut HttpResponseBuilder {
+  let origins = &ENVIRONMENT.json_cors_origins;
+
+  if origins.iter().any(|origin| origin == "*") {
+    response_builder.insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, "*"));
+  } else if !origins.is_empty() {
+    response_builder.insert_header((VARY, "Origin"));
+
+    if let Some(origin) = http_request.headers().get(ORIGIN).filter(|origin| {
+      origins.iter().any(|allowed| *origin == allowed.as_str())
+    }) {
+      response_builder
+        .insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone()));
+    }
+  }
+
+  response_builder
+}
+
+fn page_head(
+  http_request: &HttpRequest,
+  configuration: &configuration::Configuration,
+  language: &str,
+  title: &str,
+) -> String {
+  let mut html_context = format!(
+    r#"<!DOCTYPE html><html{}><head><meta name="viewport" content="width=device-width, initial-scale=1.0">"#,
+    if language.is_empty() {
+      String::new()
+    } else {
+      format!(" lang=\"{language}\"")
+    }
+  );
+
+  if !configuration.is_no_css() {
+    if let Some(css) = &ENVIRONMENT.css_external {
+      for stylesheet in css.split(',').filter(|s| !s.is_empty()) {
+        let _ = write!(
+          &mut html_context,
+          "<link rel=\"stylesheet\" type=\"text/css\" href=\"{stylesheet}\">",
+        );
+      }
+    } else {
+      let _ = write!(
+        &mut html_context,
+        r#"<link rel="stylesheet" href="https://latex.vercel.app/style.css"><style>{CSS}</style>"#
+      );
+
+      if let Some(primary) = &ENVIRONMENT.primary_colour {
+        let _ = write!(
+          &mut html_context,
+          "<style>:root {{ --primary: {primary} }}</style>"
+        );
+      } else {
+        let _ = write!(
+          &mut html_context,
+          "<style>:root {{ --primary: var(--base0D); }}</style>"
+        );
+      }
+    }
+  }
+
+  if let Some(favicon) = &ENVIRONMENT.favicon_external {
+    let _ = write!(
+      &mut html_context,
+      "<link rel=\"icon\" type=\"image/x-icon\" href=\"{favicon}\">",
+    );
+  }
+
+  if ENVIRONMENT.mathjax {
+    html_context.push_str(
+      r#"<script type="text/javascript" id="MathJax-script" async
+        src="https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-mml-chtml.js">
+    </script>"#,
+    );
+  }
+
+  if let Some(head) = &ENVIRONMENT.head {
+    html_context.push_str(head);
+  }
+
+  let _ = write!(&mut html_context, "<title>{title}</title></head><body>");
+
+  if !http_request.path().starts_with("/proxy") {
+    if let Some(header) = &ENVIRONMENT.header {
+      let _ = write!(
+        &mut html_context,
+        "<big><blockquote>{header}</blockquote></big>"
+      );
+    }
+  }
+
+  html_context
+}
+
+fn error_page(
+  http_request: &HttpRequest,
+  configuration: &configuration::Configuration,
+  status: StatusCode,
+  message: &str,
+) -> HttpResponse {
+  let heading = format!(
+    "{} {}",
+    status.as_u16(),
+    status.canonical_reason().unwrap_or("Error")
+  );
+  let mut html_context = page_head(http_request, configuration, "", &heading);
+  let _ = write!(
+    &mut html_context,
+    "<h1>{heading}</h1><p>{}</p></body></html>",
+    escape::text(message)
+  );
+
+  HttpResponse::build(status)
+    .content_type("text/html; charset=utf-8")
+    .body(html_context)
+}
+
+// Command-line clients which have not asked for HTML are answered with ANSI
+// styled text
+fn is_terminal_client(http_request: &HttpRequest) -> bool {
+  let header = |name| {
+    http_request
+      .headers()
+      .get(name)
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or_default()
+  };
+  let user_agent = header(USER_AGENT).to_lowercase();
+
+  TERMINAL_CLIENTS.iter().any(|client| user_agent.starts_with(client))
+    && !header(ACCEPT).contains("text/html")
+}
+
+// Picks the form the client most prefers. Wildcards and ties are left to HTML,
+// so browsers are unaffected.
+fn negotiate(http_request: &HttpRequest) -> Option<&'static str> {
+  let accept = http_request.headers().get(ACCEPT)?.to_str().ok()?;
+  let quality = |form: &str| {
+    accept
+      .split(',')
+      .filter_map(|range| {
+        let mut parameters = range.split(';');
+        let media_type = parameters.next()?.trim();
+
+        media_type.eq_ignore_ascii_case(form).then(|| {
+          parameters
+            .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+            .find_map(|quality| quality.trim().parse::<f32>().ok())
+            .unwrap_or(1.0)
+        })
+      })
+      .reduce(f32::max)
+      .unwrap_or(0.0)
+  };
+
+  FORMS
+    .iter()
+    .map(|form| (*form, quality(form)))
+    .filter(|(_, quality)| *quality > 0.0)
+    .reduce(|best, form| if form.1 > best.1 { form } else { best })
+    .map(|(form, _)| form)
+    .filter(|form| *form != "text/html")
+}
+
+#[allow(clippy::future_not_send)]
 pub async fn default(
   http_request: actix_web::HttpRequest,
   input_submission: Option<actix_web::web::Form<InputSubmission>>,
 ) -> Result<HttpResponse, Error> {
-  if ["/proxy", "/proxy/", "/x", "/x/", "/raw", "/raw/", "/nocss", "/nocss/"]
-    .contains(&http_request.path())
+  let mut response = respond(http_request, input_submission).await?;
+
+  response.headers_mut().append(
+    VARY,
+    HeaderValue::from_static(if ENVIRONMENT.terminal_rendering {
+      "Accept, User-Agent"
+    } else {
+      "Accept"
+    }),
+  );
+
+  Ok(response)
+}
+
+#[allow(clippy::future_not_send, clippy::too_many_lines)]
+async fn respond(
+  http_request: actix_web::HttpRequest,
+  input_submission: Option<actix_web::web::Form<InputSubmission>>,
+) -> Result<HttpResponse, Error> {
+  if [
+    "/proxy", "/proxy/", "/x", "/x/", "/raw", "/raw/", "/nocss", "/nocss/",
+    "/json", "/json/", "/md", "/md/", "/txt", "/txt/", "/term", "/term/",
+  ]
+  .contains(&http_request.path())
   {
     return Ok(HttpResponse::Ok()
         .content_type("text/html")
       .body(r"<h1>September</h1>
 <p>This is a proxy path. Specify a Gemini URL without the protocol (<code>gemini://</code>) to proxy it.</p>
 <p>To proxy <code>gemini://fuwn.me/uptime</code>, visit <code>https://fuwn.me/proxy/fuwn.me/uptime</code>.</p>
-<p>Additionally, you may visit <code>/raw</code> to view the raw Gemini content, or <code>/nocss</code> to view the content without CSS.</p>
+<p>Additionally, you may visit <code>/raw</code> to view the raw Gemini content, <code>/nocss</code> to view the content without CSS, <code>/json</code> to view the parsed content as JSON, <code>/md</code> to view the content as Markdown, <code>/txt</code> to view the content as plain text, or <code>/term</code> to view the content as ANSI styled text.</p>
+<p>Any page may also be requested in these forms by sending an <code>Accept</code> header of <code>text/gemini</code>, <code>text/markdown</code>, <code>text/plain</code>, or <code>application/json</code>.</p>
       "));
   }
 
@@ -78,6 +333,27 @@ pub async fn default(
     }
   };
 
+  // Redirects keep the route the visitor asked for, as the negotiated form
+  // belongs to this request alone
+  let route_configuration = configuration.clone();
+
+  // Route prefixes take precedence over the Accept header
+  if !configuration.is_textual()
+    && !configuration.is_json()
+    && !configuration.is_no_css()
+  {
+    match negotiate(&http_request) {
+      Some("text/gemini") => configuration.set_raw(true),
+      Some("text/markdown") => configuration.set_markdown(true),
+      Some("text/plain") => configuration.set_text(true),
+      Some("application/json") => configuration.set_json(true),
+      _ if ENVIRONMENT.terminal_rendering
+        && is_terminal_client(&http_request) =>
+        configuration.set_terminal(true),
+      _ => {}
+    }
+  }
+
   if let Some(target) = submitted_target {
     if let Ok(parsed_target) = url::Url::parse(&target) {
       if parsed_target.scheme() == "gemini" {
@@ -96,152 +372,191 @@ pub async fn default(
     url.set_query(Some(&input));
   }
 
+  let session = identity::session(&http_request);
+  let is_submission = http_request.method() == actix_web::http::Method::POST
+    && input_submission.is_some();
   let mut timer = Instant::now();
-  let mut response = match germ::request::request(&url).await {
-    Ok(response) => response,
+  let is_raw = configuration.is_raw();
+  let is_json = configuration.is_json();
+  let is_ranged = http_request.headers().contains_key(RANGE);
+  // Byte ranges are answered from a complete body, so they are never streamed
+  let streamable = move |response: &crate::gemini::Response| {
+    response.is_streamable()
+      && (response.is_binary() || is_raw)
+      && !is_json
+      && !is_ranged
+  };
+  let fetched = if ENVIRONMENT.http_redirects {
+    crate::gemini::request(&url, session.as_deref(), is_submission, streamable)
+      .await
+      .and_then(|(response, body)| {
+        let redirects =
+          crate::gemini::Redirect::from_response(&url, &response)?
+            .into_iter()
+            .collect();
+
+        Ok((response, redirects, body))
+      })
+  } else {
+    crate::gemini::request_following(
+      &url,
+      session.as_deref(),
+      is_submission,
+      streamable,
+    )
+    .await
+  };
+  let (response, redirects, body) = match fetched {
+    Ok(fetched) => fetched,
+    Err(e) if configuration.is_json() => {
+      return Ok(
+        cross_origin(&http_request, &mut HttpResponse::BadGateway())
+          .content_type("application/json")
+          .body(crate::json::error(&e.to_string())),
+      );
+    }
     Err(e) => {
-      return Ok(HttpResponse::Ok().body(e.to_string()));
+      return Ok(error_page(
+        &http_request,
+        &configuration,
+        StatusCode::BAD_GATEWAY,
+        &e.to_string(),
+      ));
     }
   };
-  let mut redirect_response_status = None;
-  let mut redirect_url = None;
-
-  if *response.status() == germ::request::Status::PermanentRedirect
-    || *response.status() == germ::request::Status::TemporaryRedirect
-  {
-    redirect_response_status = Some(*response.status());
-    redirect_url = Some(
-      url::Url::parse(&if response.meta().starts_with('/') {
-        format!(
-          "gemini://{}{}",
-          url.domain().unwrap_or_default(),
-          response.meta()
-        )
-      } else {
-        response.meta().to_string()
-      })
-      .unwrap(),
-    );
-    response =
-      match germ::request::request(&redirect_url.clone().unwrap()).await {
-        Ok(response) => response,
-        Err(e) => {
-          return Ok(HttpResponse::Ok().body(e.to_string()));
-        }
-      }
-  }
-
+  let redirect_url = redirects.last().map(|redirect| redirect.target().clone());
   let response_time_taken = timer.elapsed();
   let meta = germ::meta::Meta::from_string(response.meta().to_string());
-  let charset = meta
+  let language = meta
     .parameters()
-    .get("charset")
-    .map_or_else(|| "utf-8".to_string(), ToString::to_string);
-  let language =
-    meta.parameters().get("lang").map_or_else(String::new, ToString::to_string);
+    .get("lang")
+    .map_or_else(String::new, |language| escape::attribute(language));
 
   timer = Instant::now();
 
-  if response.meta().starts_with("image/") {
-    if let Some(content_bytes) = &response.content_bytes() {
+  if ENVIRONMENT.http_redirects {
+    if let Some(redirect) = redirects.first() {
       return Ok(
-        HttpResponse::build(actix_web::http::StatusCode::OK)
-          .content_type(response.meta().as_ref())
-          .body(content_bytes.to_vec()),
+        HttpResponse::build(if redirect.is_permanent() {
+          StatusCode::MOVED_PERMANENTLY
+        } else {
+          StatusCode::FOUND
+        })
+        .insert_header((
+          actix_web::http::header::LOCATION,
+          crate::url::to_path(redirect.target(), &route_configuration),
+        ))
+        .finish(),
       );
     }
   }
 
-  if *response.status() == germ::request::Status::Input
-    || *response.status() == germ::request::Status::SensitiveInput
-  {
-    if configuration.is_raw() {
-      return Ok(
-        HttpResponse::Ok()
-          .content_type(format!("text/plain; charset={charset}"))
-          .body(response.meta().to_string()),
-      );
+  if configuration.is_json() {
+    return Ok(
+      cross_origin(
+        &http_request,
+        &mut status::builder(*response.status(), response.meta()),
+      )
+      .content_type("application/json")
+      .body(crate::json::from_response(
+        &response,
+        &redirects,
+        redirect_url.as_ref().unwrap_or(&url),
+        &configuration,
+        response_time_taken,
+      )),
+    );
+  }
+
+  if let Some(body) = body {
+    let mut response_builder = HttpResponse::Ok();
+
+    if response.is_binary() {
+      response_builder
+        .content_type(response.meta())
+        .insert_header((ACCEPT_RANGES, "bytes"))
+        .insert_header(content_disposition(
+          redirect_url.as_ref().unwrap_or(&url),
+        ));
+    } else {
+      response_builder
+        .content_type(format!("{}; charset=utf-8", response.mime()));
     }
 
-    let mut html_context = format!(
-      r#"<!DOCTYPE html><html{}><head><meta name="viewport" content="width=device-width, initial-scale=1.0">"#,
-      if language.is_empty() {
-        String::new()
-      } else {
-        format!(" lang=\"{language}\"")
-      }
+    return Ok(
+      response_builder
+        .insert_header((CONTENT_SECURITY_POLICY, SANDBOX))
+        .insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .streaming(body),
     );
+  }
 
-    if !configuration.is_no_css() {
-      if let Some(css) = &ENVIRONMENT.css_external {
-        for stylesheet in css.split(',').filter(|s| !s.is_empty()) {
-          let _ = write!(
-            &mut html_context,
-            "<link rel=\"stylesheet\" type=\"text/css\" href=\"{stylesheet}\">",
-          );
+  if response.is_binary() {
+    let content = response.content_bytes().unwrap_or_default();
+    let etag = range::etag(content);
+    let (mut response_builder, body) =
+      match range::requested(&http_request, content.len(), &etag) {
+        range::Range::Full => (HttpResponse::Ok(), content),
+        range::Range::Partial(range) => {
+          let mut response_builder = HttpResponse::PartialContent();
+
+          response_builder.insert_header((
+            CONTENT_RANGE,
+            format!(
+              "bytes {}-{}/{}",
+              range.start(),
+              range.end(),
+              content.len()
+            ),
+          ));
+
+          (response_builder, &content[range])
         }
-      } else {
-        let _ = write!(
-          &mut html_context,
-          r#"<link rel="stylesheet" href="https://latex.vercel.app/style.css"><style>{CSS}</style>"#
-        );
-
-        if let Some(primary) = &ENVIRONMENT.primary_colour {
-          let _ = write!(
-            &mut html_context,
-            "<style>:root {{ --primary: {primary} }}</style>"
-          );
-        } else {
-          let _ = write!(
-            &mut html_context,
-            "<style>:root {{ --primary: var(--base0D); }}</style>"
+        range::Range::Unsatisfiable => {
+          return Ok(
+            HttpResponse::RangeNotSatisfiable()
+              .insert_header((
+                CONTENT_RANGE,
+                format!("bytes */{}", content.len()),
+              ))
+              .finish(),
           );
         }
-      }
-    }
+      };
 
-    if let Some(favicon) = &ENVIRONMENT.favicon_external {
-      let _ = write!(
-        &mut html_context,
-        "<link rel=\"icon\" type=\"image/x-icon\" href=\"{favicon}\">",
-      );
-    }
+    return Ok(
+      response_builder
+        .content_type(response.meta())
+        .insert_header((ACCEPT_RANGES, "bytes"))
+        .insert_header((ETAG, etag))
+        .insert_header((CONTENT_SECURITY_POLICY, SANDBOX))
+        .insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .insert_header(content_disposition(
+          redirect_url.as_ref().unwrap_or(&url),
+        ))
+        .body(body.to_vec()),
+    );
+  }
 
-    if let Some(head) = &ENVIRONMENT.head {
-      html_context.push_str(head);
+  if *response.status() == germ::request::Status::Input
+    || *response.status() == germ::request::Status::SensitiveInput
+  {
+    if configuration.is_textual() {
+      return Ok(
+        HttpResponse::Ok()
+          .content_type("text/plain; charset=utf-8")
+          .body(response.meta().to_string()),
+      );
     }
 
-    let _ = write!(
-      &mut html_context,
-      "<title>{}</title></head><body>",
-      html_escape(&response.meta()),
+    let mut html_context = page_head(
+      &http_request,
+      &configuration,
+      &language,
+      &escape::text(response.meta()),
     );
 
-    if !http_request.path().starts_with("/proxy") {
-      if let Some(header) = &ENVIRONMENT.header {
-        let _ = write!(
-          &mut html_context,
-          "<big><blockquote>{header}</blockquote></big>"
-        );
-      }
-    }
-
-    if let (Some(status), Some(redirected_to)) =
-      (redirect_response_status, redirect_url.clone())
-    {
-      let _ = write!(
-        &mut html_context,
-        "<blockquote>This page {} redirects to <a \
-         href=\"{}\">{}</a>.</blockquote>",
-        if status == germ::request::Status::PermanentRedirect {
-          "permanently"
-        } else {
-          "temporarily"
-        },
-        redirected_to,
-        redirected_to
-      );
-    }
+    html_context.push_str(&redirect_notice(&redirects));
 
     let input_url = redirect_url.unwrap_or_else(|| url.clone());
     let input_field =
@@ -255,9 +570,9 @@ pub async fn default(
       "<p>{}</p><form method=\"post\" action=\"{}\"><input type=\"hidden\" \
        name=\"target\" value=\"{}\">{}<button \
        type=\"submit\">Submit</button></form></body></html>",
-      html_escape(&response.meta()),
-      html_escape(&http_request.uri().to_string()),
-      html_escape(input_url.as_ref()),
+      escape::text(response.meta()),
+      escape::attribute(&http_request.uri().to_string()),
+      escape::attribute(input_url.as_ref()),
       input_field,
     );
     let mut response_builder = HttpResponse::Ok();
@@ -269,130 +584,193 @@ pub async fn default(
 
     return Ok(
       response_builder
-        .content_type(format!("text/html; charset={charset}"))
+        .content_type("text/html; charset=utf-8")
         .body(html_context),
     );
   }
 
-  let mut html_context = if configuration.is_raw() {
-    String::new()
+  let final_url = redirect_url.as_ref().unwrap_or(&url);
+  // The body is decoded once, and shared by every renderer below
+  let (content, encoding_notice) = response.decode().unwrap_or_default();
+  let is_gemtext = *response.status() == germ::request::Status::Success
+    && response.mime() == "text/gemini";
+  let converted = if !is_gemtext {
+    None
+  } else if configuration.is_markdown() {
+    Some(("text/markdown", crate::markdown::from_gemtext(&content, final_url)))
+  } else if configuration.is_text() {
+    Some(("text/plain", crate::text::from_gemtext(&content, final_url)))
+  } else if configuration.is_terminal() {
+    Some(("text/plain", crate::text::to_terminal(&content, final_url)))
   } else {
-    format!(
-      r#"<!DOCTYPE html><html{}><head><meta name="viewport" content="width=device-width, initial-scale=1.0">"#,
-      if language.is_empty() {
-        String::new()
-      } else {
-        format!(" lang=\"{language}\"")
-      }
-    )
+    None
   };
-  let gemini_html =
-    crate::html::from_gemini(&response, &url, &configuration).unwrap();
-  let gemini_title = gemini_html.0;
-  let convert_time_taken = timer.elapsed();
-
-  if configuration.is_raw() {
-    html_context.push_str(
-      &response.content().as_ref().map_or_else(String::default, String::clone),
-    );
 
+  if let Some((mime, converted)) = converted {
     return Ok(
       HttpResponse::Ok()
-        .content_type(format!("{}; charset={charset}", meta.mime()))
-        .body(html_context),
+        .content_type(format!("{mime}; charset=utf-8"))
+        .insert_header((CONTENT_SECURITY_POLICY, SANDBOX))
+        .insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .body(converted),
     );
   }
 
-  if configuration.is_no_css() {
-    html_context.push_str(&gemini_html.1);
-
+  if configuration.is_textual()
+    && *response.status() != germ::request::Status::Success
+  {
     return Ok(
-      HttpResponse::Ok()
-        .content_type(format!("text/html; charset={charset}"))
-        .body(html_context),
+      status::builder(*response.status(), response.meta())
+        .content_type("text/plain; charset=utf-8")
+        .insert_header((CONTENT_SECURITY_POLICY, SANDBOX))
+        .insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .body(crate::text::from_status(
+          *response.status(),
+          response.meta(),
+          status::explanation(*response.status()),
+        )),
     );
   }
 
-  if let Some(css) = &ENVIRONMENT.css_external {
-    for stylesheet in css.split(',').filter(|s| !s.is_empty()) {
-      let _ = write!(
-        &mut html_context,
-        "<link rel=\"stylesheet\" type=\"text/css\" href=\"{stylesheet}\">",
-      );
-    }
-  } else if !configuration.is_no_css() {
-    let _ = write!(
-      &mut html_context,
-      r#"<link rel="stylesheet" href="https://latex.vercel.app/style.css"><style>{CSS}</style>"#
+  // Anything else which cannot be converted is passed on as it is
+  if configuration.is_textual() {
+    return Ok(
+      status::builder(*response.status(), response.meta())
+        .content_type(format!("{}; charset=utf-8", meta.mime()))
+        .insert_header((CONTENT_SECURITY_POLICY, SANDBOX))
+        .insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .body(content),
     );
-
-    if let Some(primary) = &ENVIRONMENT.primary_colour {
-      let _ = write!(
-        &mut html_context,
-        "<style>:root {{ --primary: {primary} }}</style>"
-      );
-    } else {
-      let _ = write!(
-        &mut html_context,
-        "<style>:root {{ --primary: var(--base0D); }}</style>"
-      );
-    }
   }
 
-  if let Some(favicon) = &ENVIRONMENT.favicon_external {
-    let _ = write!(
-      &mut html_context,
-      "<link rel=\"icon\" type=\"image/x-icon\" href=\"{favicon}\">",
+  let Some(gemini_html) = crate::html::from_response(
+    &response,
+    &content,
+    redirect_url.as_ref().unwrap_or(&url),
+    &configuration,
+  ) else {
+    return Ok(error_page(
+      &http_request,
+      &configuration,
+      StatusCode::BAD_GATEWAY,
+      "could not render the response",
+    ));
+  };
+  let gemini_title = gemini_html.0;
+  let convert_time_taken = timer.elapsed();
+
+  if configuration.is_no_css() {
+    let html_context = format!(
+      r#"<!DOCTYPE html><html{}><head><meta name="viewport" content="width=device-width, initial-scale=1.0">{}"#,
+      if language.is_empty() {
+        String::new()
+      } else {
+        format!(" lang=\"{language}\"")
+      },
+      gemini_html.1
     );
-  }
 
-  if ENVIRONMENT.mathjax {
-    html_context.push_str(
-      r#"<script type="text/javascript" id="MathJax-script" async
-        src="https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-mml-chtml.js">
-    </script>"#,
+    return Ok(
+      status::builder(*response.status(), response.meta())
+        .content_type("text/html; charset=utf-8")
+        .body(html_context),
     );
   }
 
-  if let Some(head) = &ENVIRONMENT.head {
-    html_context.push_str(head);
-  }
+  let http_status = status::to_http(*response.status());
+  let is_redirect = matches!(
+    response.status(),
+    germ::request::Status::TemporaryRedirect
+      | germ::request::Status::PermanentRedirect
+  );
+  let title = if *response.status() == germ::request::Status::Success {
+    gemini_title
+  } else if is_redirect {
+    "Redirect".to_string()
+  } else {
+    format!(
+      "{} {}",
+      http_status.as_u16(),
+      http_status.canonical_reason().unwrap_or("Error")
+    )
+  };
+  let mut html_context =
+    page_head(&http_request, &configuration, &language, &title);
+  let mut information = String::new();
 
-  let _ = write!(&mut html_context, "<title>{gemini_title}</title>");
-  let _ = write!(&mut html_context, "</head><body>");
+  html_context.push_str(&redirect_notice(&redirects));
 
-  if !http_request.path().starts_with("/proxy") {
-    if let Some(header) = &ENVIRONMENT.header {
+  match response.status() {
+    germ::request::Status::Success => {
+      html_context.push_str(&gemini_html.1);
+    }
+    // Only redirects to other schemes are left unfollowed
+    _ if is_redirect => {
       let _ = write!(
         &mut html_context,
-        "<big><blockquote>{header}</blockquote></big>"
+        "<h1>{title}</h1><p>This page redirects to <a href=\"{}\">{}</a>, \
+         which is not a Gemini address, so September has not followed it.</p>",
+        escape::href(redirect_url.as_ref().map_or("", url::Url::as_str)),
+        escape::text(redirect_url.as_ref().map_or("", url::Url::as_str)),
       );
     }
-  }
+    status => {
+      let _ = write!(&mut html_context, "<h1>{title}</h1>");
 
-  match response.status() {
-    germ::request::Status::Success => {
-      if let (Some(status), Some(url)) =
-        (redirect_response_status, redirect_url)
-      {
+      if let Some(explanation) = status::explanation(*status) {
         let _ = write!(
           &mut html_context,
-          "<blockquote>This page {} redirects to <a \
-           href=\"{}\">{}</a>.</blockquote>",
-          if status == germ::request::Status::PermanentRedirect {
-            "permanently"
-          } else {
-            "temporarily"
-          },
-          url,
-          url
+          "<p>{explanation}</p><blockquote>{}</blockquote>",
+          escape::text(response.meta())
         );
+      } else {
+        let _ =
+          write!(&mut html_context, "<p>{}</p>", escape::text(response.meta()));
       }
 
-      html_context.push_str(&gemini_html.1);
+      if ENVIRONMENT.ephemeral_identities
+        && status::explanation(*status).is_some()
+      {
+        html_context.push_str(&identity::controls(
+          &http_request,
+          session.as_deref(),
+          final_url,
+          *status == germ::request::Status::ClientCertificateRequired,
+        ));
+      }
     }
-    _ => {
-      let _ = write!(&mut html_context, "<p>{}</p>", response.meta());
+  }
+
+  if let Some(notice) = encoding_notice {
+    let _ = writeln!(
+      &mut information,
+      "<dt>Character Encoding</dt><dd>{}</dd>",
+      escape::text(&notice)
+    );
+  }
+
+  if let Some(certificate) = response.certificate() {
+    let _ = writeln!(
+      &mut information,
+      "<dt>Certificate Fingerprint</dt><dd><code>{}</code></dd>
+<dt>Certificate Expiry</dt><dd>{}</dd>
+<dt>Certificate Trust</dt><dd>{}</dd>",
+      certificate.fingerprint(),
+      escape::text(certificate.expiry().unwrap_or("Unknown")),
+      certificate.trust(),
+    );
+  }
+
+  if ENVIRONMENT.ephemeral_identities {
+    let controls =
+      identity::controls(&http_request, session.as_deref(), final_url, false);
+
+    if !controls.is_empty() {
+      let _ = writeln!(
+        &mut information,
+        "<dt>Identity</dt><dd>An ephemeral identity was presented to this \
+         capsule.{controls}</dd>"
+      );
     }
   }
 
@@ -405,16 +783,17 @@ pub async fn default(
 <dt>Meta</dt><dd><code>{}</code></dd>
 <dt>Capsule Response Time</dt><dd>{} milliseconds</dd>
 <dt>Gemini-to-HTML Time</dt><dd>{} milliseconds</dd>
-</dl>
+{}</dl>
 <p>This content has been proxied by <a \
      href=\"https://github.com/gemrest/september{}\">September ({})</a>.</p>
 </details></body></html>",
-    url,
+    escape::attribute(url.as_str()),
     response.status(),
     i32::from(*response.status()),
-    response.meta(),
+    escape::text(response.meta()),
     response_time_taken.as_nanos() as f64 / 1_000_000.0,
     convert_time_taken.as_nanos() as f64 / 1_000_000.0,
+    information,
     format_args!("/tree/{}", env!("VERGEN_GIT_SHA")),
     env!("VERGEN_GIT_SHA").get(0..5).unwrap_or("UNKNOWN"),
   );
@@ -424,15 +803,55 @@ pub async fn default(
       matches_pattern(r, http_request.path())
         || matches_pattern(r, http_request.path().trim_end_matches('/'))
     }) {
-      return Ok(HttpResponse::Ok().body(
-        response.content().as_ref().map_or_else(String::default, String::clone),
-      ));
+      return Ok(
+        status::builder(*response.status(), response.meta())
+          .insert_header((CONTENT_SECURITY_POLICY, SANDBOX))
+          .insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+          .body(content),
+      );
     }
   }
 
   Ok(
-    HttpResponse::Ok()
-      .content_type(format!("text/html; charset={charset}"))
+    status::builder(*response.status(), response.meta())
+      .content_type("text/html; charset=utf-8")
       .body(html_context),
   )
 }
+
+#[allow(clippy::future_not_send, clippy::unused_async)]
+pub async fn purge(
+  http_request: actix_web::HttpRequest,
+) -> Result<HttpResponse, Error> {
+  let authorised =
+    ENVIRONMENT.cache_purge_token.as_ref().is_some_and(|token| {
+      http_request
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        == Some(&format!("Bearer {token}"))
+    });
+
+  if !authorised {
+    return Ok(HttpResponse::Unauthorized().finish());
+  }
+
+  let path = http_request.path().trim_start_matches("/purge");
+
+  if path.is_empty() {
+    CACHE.purge_all();
+  } else {
+    match url_from_path(path, false, &mut configuration::Configuration::new()) {
+      Ok(url) => CACHE.purge(&url),
+      Err(e) => {
+        return Ok(
+          HttpResponse::BadRequest()
+            .content_type("text/plain")
+            .body(format!("{e}")),
+        );
+      }
+    }
+  }
+
+  Ok(HttpResponse::NoContent().finish())
+}
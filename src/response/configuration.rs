@@ -1,13 +1,26 @@
-#[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Default)]
 pub struct Configuration {
-  is_proxy:  bool,
-  is_raw:    bool,
-  is_no_css: bool,
+  is_proxy:    bool,
+  is_raw:      bool,
+  is_no_css:   bool,
+  is_json:     bool,
+  is_markdown: bool,
+  is_text:     bool,
+  is_terminal: bool,
 }
 
 impl Configuration {
   pub const fn new() -> Self {
-    Self { is_proxy: false, is_raw: false, is_no_css: false }
+    Self {
+      is_proxy:    false,
+      is_raw:      false,
+      is_no_css:   false,
+      is_json:     false,
+      is_markdown: false,
+      is_text:     false,
+      is_terminal: false,
+    }
   }
 
   pub const fn is_proxy(&self) -> bool { self.is_proxy }
@@ -16,6 +29,19 @@ impl Configuration {
 
   pub const fn is_no_css(&self) -> bool { self.is_no_css }
 
+  pub const fn is_json(&self) -> bool { self.is_json }
+
+  pub const fn is_markdown(&self) -> bool { self.is_markdown }
+
+  pub const fn is_text(&self) -> bool { self.is_text }
+
+  pub const fn is_terminal(&self) -> bool { self.is_terminal }
+
+  // Modes which answer with the content itself rather than an HTML page
+  pub const fn is_textual(&self) -> bool {
+    self.is_raw || self.is_markdown || self.is_text || self.is_terminal
+  }
+
   pub const fn set_proxy(&mut self, is_proxy: bool) {
     self.is_proxy = is_proxy;
   }
@@ -25,4 +51,16 @@ impl Configuration {
   pub const fn set_no_css(&mut self, is_no_css: bool) {
     self.is_no_css = is_no_css;
   }
+
+  pub const fn set_json(&mut self, is_json: bool) { self.is_json = is_json; }
+
+  pub const fn set_markdown(&mut self, is_markdown: bool) {
+    self.is_markdown = is_markdown;
+  }
+
+  pub const fn set_text(&mut self, is_text: bool) { self.is_text = is_text; }
+
+  pub const fn set_terminal(&mut self, is_terminal: bool) {
+    self.is_terminal = is_terminal;
+  }
 }
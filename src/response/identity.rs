@@ -0,0 +1,148 @@
+use {
+  crate::{gemini::certificate, html::escape},
+  actix_web::{
+    Error, HttpRequest, HttpResponse,
+    cookie::{Cookie, SameSite},
+    http::header::LOCATION,
+    web,
+  },
+  url::Url,
+};
+
+pub const SESSION_COOKIE: &str = "september_session";
+
+#[derive(serde::Deserialize)]
+pub struct IdentitySubmission {
+  target:    String,
+  #[serde(rename = "return")]
+  return_to: String,
+}
+
+pub fn session(http_request: &HttpRequest) -> Option<String> {
+  http_request.cookie(SESSION_COOKIE).map(|cookie| cookie.value().to_string())
+}
+
+pub fn controls(
+  http_request: &HttpRequest,
+  session: Option<&str>,
+  url: &Url,
+  offer_creation: bool,
+) -> String {
+  let (action, label) = if session
+    .and_then(|session| certificate::ephemeral(session, url))
+    .is_some()
+  {
+    ("delete", "Delete your identity for this capsule")
+  } else if offer_creation {
+    ("create", "Create an identity for this capsule")
+  } else {
+    return String::new();
+  };
+
+  format!(
+    "<form method=\"post\" action=\"/identity/{action}\"><input \
+     type=\"hidden\" name=\"target\" value=\"{}\"><input type=\"hidden\" \
+     name=\"return\" value=\"{}\"><button \
+     type=\"submit\">{label}</button></form>",
+    escape::attribute(url.as_str()),
+    escape::attribute(&http_request.uri().to_string()),
+  )
+}
+
+fn parse_submission(
+  submission: &IdentitySubmission,
+) -> Result<(Url, String), &'static str> {
+  let target = Url::parse(&submission.target)
+    .ok()
+    .filter(|target| target.scheme() == "gemini")
+    .ok_or("invalid identity target")?;
+  // Only return to local paths so that the form cannot be used as an open
+  // redirect
+  let return_to = if submission.return_to.starts_with('/')
+    && !submission.return_to.starts_with("//")
+  {
+    submission.return_to.clone()
+  } else {
+    crate::url::to_path(
+      &target,
+      &super::configuration::Configuration::default(),
+    )
+  };
+
+  Ok((target, return_to))
+}
+
+#[allow(clippy::future_not_send, clippy::unused_async)]
+pub async fn create(
+  http_request: HttpRequest,
+  submission: web::Form<IdentitySubmission>,
+) -> Result<HttpResponse, Error> {
+  let (target, return_to) = match parse_submission(&submission) {
+    Ok(parsed) => parsed,
+    Err(e) => {
+      return Ok(HttpResponse::BadRequest().content_type("text/plain").body(e));
+    }
+  };
+  let session =
+    match session(&http_request).map_or_else(certificate::new_session, Ok) {
+      Ok(session) => session,
+      Err(e) => {
+        return Ok(
+          HttpResponse::InternalServerError()
+            .content_type("text/plain")
+            .body(e.to_string()),
+        );
+      }
+    };
+
+  match certificate::create_ephemeral(&session, &target) {
+    Ok(true) => {}
+    Ok(false) => {
+      return Ok(
+        HttpResponse::ServiceUnavailable()
+          .content_type("text/plain")
+          .body("too many identities are in use; try again later"),
+      );
+    }
+    Err(e) => {
+      return Ok(
+        HttpResponse::InternalServerError()
+          .content_type("text/plain")
+          .body(e.to_string()),
+      );
+    }
+  }
+
+  Ok(
+    HttpResponse::SeeOther()
+      .insert_header((LOCATION, return_to))
+      .cookie(
+        Cookie::build(SESSION_COOKIE, session)
+          .path("/")
+          .http_only(true)
+          .same_site(SameSite::Lax)
+          .secure(http_request.connection_info().scheme() == "https")
+          .finish(),
+      )
+      .finish(),
+  )
+}
+
+#[allow(clippy::future_not_send, clippy::unused_async)]
+pub async fn delete(
+  http_request: HttpRequest,
+  submission: web::Form<IdentitySubmission>,
+) -> Result<HttpResponse, Error> {
+  let (target, return_to) = match parse_submission(&submission) {
+    Ok(parsed) => parsed,
+    Err(e) => {
+      return Ok(HttpResponse::BadRequest().content_type("text/plain").body(e));
+    }
+  };
+
+  if let Some(session) = session(&http_request) {
+    certificate::delete_ephemeral(&session, &target);
+  }
+
+  Ok(HttpResponse::SeeOther().insert_header((LOCATION, return_to)).finish())
+}
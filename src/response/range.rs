@@ -0,0 +1,179 @@
+use {
+  actix_web::{
+    HttpRequest,
+    http::header::{IF_RANGE, RANGE},
+  },
+  std::{fmt::Write, ops::RangeInclusive},
+};
+
+pub enum Range {
+  Full,
+  Partial(RangeInclusive<usize>),
+  Unsatisfiable,
+}
+
+// Gemini responses carry no validators, so the entity tag is derived from the
+// body itself
+pub fn etag(content: &[u8]) -> String {
+  let digest = ring::digest::digest(&ring::digest::SHA256, content);
+  let mut etag = String::from("\"");
+
+  for byte in &digest.as_ref()[..16] {
+    let _ = write!(etag, "{byte:02x}");
+  }
+
+  etag.push('"');
+
+  etag
+}
+
+// Only single byte ranges are supported. Anything else is ignored, which
+// RFC 9110 permits, and is answered with the full body.
+pub fn requested(
+  http_request: &HttpRequest,
+  length: usize,
+  etag: &str,
+) -> Range {
+  let header = |name| {
+    http_request.headers().get(name).and_then(|value| value.to_str().ok())
+  };
+  let Some(range) = header(RANGE) else {
+    return Range::Full;
+  };
+
+  if header(IF_RANGE).is_some_and(|if_range| if_range.trim() != etag) {
+    return Range::Full;
+  }
+
+  let Some((start, end)) = range
+    .trim()
+    .strip_prefix("bytes=")
+    .filter(|ranges| !ranges.contains(','))
+    .and_then(|range| range.split_once('-'))
+  else {
+    return Range::Full;
+  };
+  let (start, end) = (start.trim(), end.trim());
+  let (start, end) = if start.is_empty() {
+    match end.parse::<usize>() {
+      Ok(0) => return Range::Unsatisfiable,
+      Ok(suffix) => (length.saturating_sub(suffix), length.saturating_sub(1)),
+      Err(_) => return Range::Full,
+    }
+  } else {
+    let Ok(start) = start.parse::<usize>() else {
+      return Range::Full;
+    };
+    let end = if end.is_empty() {
+      length.saturating_sub(1)
+    } else {
+      // A last position before the first is syntactically invalid, so the
+      // header is ignored rather than answered as unsatisfiable
+      match end.parse::<usize>() {
+        Ok(end) if end >= start => end.min(length.saturating_sub(1)),
+        _ => return Range::Full,
+      }
+    };
+
+    (start, end)
+  };
+
+  if length == 0 || start >= length {
+    Range::Unsatisfiable
+  } else {
+    Range::Partial(start..=end)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{Range, requested},
+    actix_web::{
+      http::header::{IF_RANGE, RANGE},
+      test::TestRequest,
+    },
+  };
+
+  const ETAG: &str = "\"etag\"";
+
+  fn range(headers: &[(&str, &str)], length: usize) -> Range {
+    let mut request = TestRequest::default();
+
+    for header in headers {
+      request = request.insert_header(*header);
+    }
+
+    requested(&request.to_http_request(), length, ETAG)
+  }
+
+  #[test]
+  fn serves_bounded_ranges() {
+    assert!(matches!(
+      range(&[(RANGE.as_str(), "bytes=2-5")], 10),
+      Range::Partial(range) if range == (2..=5)
+    ));
+    assert!(matches!(
+      range(&[(RANGE.as_str(), "bytes=8-20")], 10),
+      Range::Partial(range) if range == (8..=9)
+    ));
+  }
+
+  #[test]
+  fn serves_open_ended_ranges() {
+    assert!(matches!(
+      range(&[(RANGE.as_str(), "bytes=4-")], 10),
+      Range::Partial(range) if range == (4..=9)
+    ));
+  }
+
+  #[test]
+  fn serves_suffix_ranges() {
+    assert!(matches!(
+      range(&[(RANGE.as_str(), "bytes=-3")], 10),
+      Range::Partial(range) if range == (7..=9)
+    ));
+    assert!(matches!(
+      range(&[(RANGE.as_str(), "bytes=-20")], 10),
+      Range::Partial(range) if range == (0..=9)
+    ));
+    assert!(matches!(
+      range(&[(RANGE.as_str(), "bytes=-0")], 10),
+      Range::Unsatisfiable
+    ));
+  }
+
+  #[test]
+  fn ignores_invalid_ranges() {
+    for header in ["bytes=5-3", "bytes=0-1,4-5", "items=0-1", "bytes=a-b"] {
+      assert!(matches!(range(&[(RANGE.as_str(), header)], 10), Range::Full));
+    }
+  }
+
+  #[test]
+  fn rejects_unsatisfiable_ranges() {
+    assert!(matches!(
+      range(&[(RANGE.as_str(), "bytes=10-")], 10),
+      Range::Unsatisfiable
+    ));
+    assert!(matches!(
+      range(&[(RANGE.as_str(), "bytes=0-")], 0),
+      Range::Unsatisfiable
+    ));
+  }
+
+  #[test]
+  fn honours_if_range() {
+    assert!(matches!(
+      range(&[(RANGE.as_str(), "bytes=0-1"), (IF_RANGE.as_str(), ETAG)], 10),
+      Range::Partial(range) if range == (0..=1)
+    ));
+    assert!(matches!(
+      range(
+        &[(RANGE.as_str(), "bytes=0-1"), (IF_RANGE.as_str(), "\"other\"")],
+        10
+      ),
+      Range::Full
+    ));
+  }
+}
@@ -0,0 +1,84 @@
+use {
+  crate::environment::ENVIRONMENT,
+  actix_web::{HttpResponseBuilder, http::StatusCode},
+  germ::request::Status,
+};
+
+const RETRY_AFTER: &str = "60";
+
+const fn default_http_status(status: Status) -> StatusCode {
+  match status {
+    Status::Input
+    | Status::SensitiveInput
+    | Status::Success
+    | Status::TemporaryRedirect
+    | Status::PermanentRedirect => StatusCode::OK,
+    Status::TemporaryFailure
+    | Status::ServerUnavailable
+    | Status::CGIError
+    | Status::ProxyError
+    | Status::SlowDown => StatusCode::SERVICE_UNAVAILABLE,
+    Status::PermanentFailure => StatusCode::INTERNAL_SERVER_ERROR,
+    Status::NotFound => StatusCode::NOT_FOUND,
+    Status::Gone => StatusCode::GONE,
+    Status::ProxyRefused | Status::Unsupported => StatusCode::BAD_GATEWAY,
+    Status::BadRequest => StatusCode::BAD_REQUEST,
+    Status::ClientCertificateRequired
+    | Status::CertificateNotAuthorised
+    | Status::CertificateNotValid => StatusCode::FORBIDDEN,
+  }
+}
+
+fn matches_status(pattern: &str, code: i32) -> bool {
+  pattern.strip_suffix(['x', 'X']).map_or_else(
+    || pattern == code.to_string(),
+    |class| class == (code / 10).to_string(),
+  )
+}
+
+pub fn to_http(status: Status) -> StatusCode {
+  let code = i32::from(status);
+
+  ENVIRONMENT
+    .status_codes
+    .iter()
+    .find(|(pattern, _)| matches_status(pattern, code))
+    .and_then(|(_, http_status)| StatusCode::from_u16(*http_status).ok())
+    .unwrap_or_else(|| default_http_status(status))
+}
+
+pub fn builder(status: Status, meta: &str) -> HttpResponseBuilder {
+  let mut builder = HttpResponseBuilder::new(to_http(status));
+
+  if i32::from(status) / 10 == 4 {
+    builder.insert_header((
+      actix_web::http::header::RETRY_AFTER,
+      if status == Status::SlowDown && meta.trim().parse::<u32>().is_ok() {
+        meta.trim()
+      } else {
+        RETRY_AFTER
+      },
+    ));
+  }
+
+  builder
+}
+
+pub const fn explanation(status: Status) -> Option<&'static str> {
+  match status {
+    Status::ClientCertificateRequired => Some(
+      "This capsule requires a client certificate, and September has not been \
+       configured with one for this page.",
+    ),
+    Status::CertificateNotAuthorised => Some(
+      "This capsule recognised the client certificate presented by September, \
+       but the certificate is not authorised to access this page.",
+    ),
+    Status::CertificateNotValid => Some(
+      "This capsule rejected the client certificate presented by September as \
+       invalid. It may have expired, or it may not be a valid X.509 \
+       certificate.",
+    ),
+    _ => None,
+  }
+}
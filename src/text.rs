@@ -0,0 +1,215 @@
+use {
+  crate::{
+    environment::ENVIRONMENT,
+    html::{Output, walk},
+  },
+  std::fmt::Write,
+  url::Url,
+};
+
+// Words are never split, so a word longer than the width overflows its line
+fn wrap(text: &str, first_prefix: &str, prefix: &str) -> String {
+  let width = ENVIRONMENT.text_width;
+  let mut wrapped = String::new();
+  let mut line = first_prefix.to_string();
+  let mut length = first_prefix.chars().count();
+  let mut is_empty = true;
+
+  for word in text.split_whitespace() {
+    let word_length = word.chars().count();
+
+    if !is_empty && length + 1 + word_length > width {
+      wrapped.push_str(&line);
+      wrapped.push('\n');
+
+      line = prefix.to_string();
+      length = prefix.chars().count();
+      is_empty = true;
+    }
+
+    if !is_empty {
+      line.push(' ');
+
+      length += 1;
+    }
+
+    line.push_str(word);
+
+    length += word_length;
+    is_empty = false;
+  }
+
+  wrapped.push_str(line.trim_end());
+
+  wrapped
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Style {
+  Plain,
+  Terminal,
+}
+
+impl Style {
+  // Each line is styled on its own, so that pagers never carry an attribute
+  // over into the next line
+  fn paint(self, text: &str, code: &str) -> String {
+    match self {
+      Self::Plain => text.to_string(),
+      Self::Terminal => text
+        .lines()
+        .map(|line| format!("\x1b[{code}m{line}\x1b[0m"))
+        .collect::<Vec<_>>()
+        .join("\n"),
+    }
+  }
+}
+
+pub fn from_gemtext(content: &str, url: &Url) -> String {
+  render(content, url, Style::Plain)
+}
+
+pub fn to_terminal(content: &str, url: &Url) -> String {
+  render(content, url, Style::Terminal)
+}
+
+// Capsules must not be able to send their own escape sequences to the
+// visitor's terminal, whether it is reading plain or styled text
+fn printable(content: &str) -> String {
+  content
+    .chars()
+    .filter(|character| {
+      !character.is_control() || matches!(character, '\n' | '\t')
+    })
+    .collect()
+}
+
+// Responses other than pages are given as their status line, followed by the
+// reason for it when there is one
+pub fn from_status(
+  status: germ::request::Status,
+  meta: &str,
+  explanation: Option<&str>,
+) -> String {
+  let mut text = format!("{} {}\n", i32::from(status), printable(meta));
+
+  if let Some(explanation) = explanation {
+    let _ = write!(&mut text, "\n{}\n", wrap(explanation, "", ""));
+  }
+
+  text
+}
+
+struct TextOutput<'a> {
+  url:        &'a Url,
+  style:      Style,
+  text:       String,
+  footnotes:  String,
+  link_count: usize,
+}
+
+impl Output for TextOutput<'_> {
+  fn text(&mut self, text: &str) {
+    let _ = writeln!(&mut self.text, "{}", wrap(text, "", ""));
+  }
+
+  fn heading(&mut self, level: usize, text: &str) {
+    let heading = wrap(text, "", "");
+
+    let _ = writeln!(
+      &mut self.text,
+      "{}",
+      self.style.paint(&heading, if level == 1 { "1;4" } else { "1" })
+    );
+
+    if let Some(underline) = match level {
+      _ if self.style == Style::Terminal => None,
+      1 => Some('='),
+      2 => Some('-'),
+      _ => None,
+    } {
+      let length = heading.lines().map(|line| line.chars().count()).max();
+
+      let _ = writeln!(
+        &mut self.text,
+        "{}",
+        underline.to_string().repeat(length.unwrap_or_default())
+      );
+    }
+  }
+
+  fn list(&mut self, items: &[String]) {
+    for item in items {
+      let _ = writeln!(&mut self.text, "{}", wrap(item, "* ", "  "));
+    }
+  }
+
+  fn blockquote(&mut self, text: &str) {
+    let _ = writeln!(
+      &mut self.text,
+      "{}",
+      self.style.paint(&wrap(text, "> ", "> "), "2")
+    );
+  }
+
+  fn preformatted(&mut self, text: &str, _alt_text: Option<&str>) {
+    self.text.push_str(text);
+
+    if !text.ends_with('\n') {
+      self.text.push('\n');
+    }
+  }
+
+  fn link(&mut self, to: &str, text: Option<&str>) -> Option<()> {
+    let target =
+      self.url.join(to).map_or_else(|_| to.to_string(), String::from);
+
+    self.link_count += 1;
+
+    let _ = writeln!(
+      &mut self.text,
+      "{}",
+      self.style.paint(
+        &wrap(
+          &format!("{} [{}]", text.unwrap_or(&target), self.link_count),
+          "",
+          ""
+        ),
+        "36"
+      )
+    );
+    let _ = writeln!(
+      &mut self.footnotes,
+      "{} {target}",
+      self.style.paint(&format!("[{}]", self.link_count), "36")
+    );
+
+    Some(())
+  }
+
+  fn whitespace(&mut self) { self.text.push('\n'); }
+}
+
+fn render(content: &str, url: &Url, style: Style) -> String {
+  let mut output = TextOutput {
+    url,
+    style,
+    text: String::new(),
+    footnotes: String::new(),
+    link_count: 0,
+  };
+
+  // Text output has no link which it cannot write, so the walk always
+  // completes
+  let _ = walk(&printable(content), &mut output);
+
+  let TextOutput { mut text, footnotes, .. } = output;
+
+  if !footnotes.is_empty() {
+    text.truncate(text.trim_end().len());
+
+    let _ = write!(&mut text, "\n\n{footnotes}");
+  }
+
+  text
+}
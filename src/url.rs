@@ -39,6 +39,44 @@ pub fn from_path(
       path.replace("/nocss/", ""),
       if fallback { "/" } else { "" }
     )
+  } else if path.starts_with("/json/") {
+    configuration.set_proxy(true);
+    configuration.set_json(true);
+
+    format!(
+      "gemini://{}{}",
+      path.replace("/json/", ""),
+      if fallback { "/" } else { "" }
+    )
+  } else if path.starts_with("/md/") {
+    configuration.set_proxy(true);
+    configuration.set_markdown(true);
+
+    format!(
+      "gemini://{}{}",
+      path.replace("/md/", ""),
+      if fallback { "/" } else { "" }
+    )
+  } else if path.starts_with("/txt/") {
+    configuration.set_proxy(true);
+    configuration.set_text(true);
+
+    format!(
+      "gemini://{}{}",
+      path.replace("/txt/", ""),
+      if fallback { "/" } else { "" }
+    )
+  } else if path.starts_with("/term/")
+    && crate::environment::ENVIRONMENT.terminal_rendering
+  {
+    configuration.set_proxy(true);
+    configuration.set_terminal(true);
+
+    format!(
+      "gemini://{}{}",
+      path.replace("/term/", ""),
+      if fallback { "/" } else { "" }
+    )
   } else {
     format!(
       "{}{}{}",
@@ -49,6 +87,56 @@ pub fn from_path(
   })
 }
 
+pub fn to_path(
+  url: &Url,
+  configuration: &crate::response::configuration::Configuration,
+) -> String {
+  if url.scheme() != "gemini" {
+    return url.to_string();
+  }
+
+  if !configuration.is_raw()
+    && !configuration.is_no_css()
+    && !configuration.is_json()
+    && !configuration.is_markdown()
+    && !configuration.is_text()
+    && !configuration.is_terminal()
+  {
+    if let Some(path) = url
+      .as_str()
+      .strip_prefix(crate::environment::ENVIRONMENT.root.trim_end_matches('/'))
+    {
+      if path.is_empty() || path.starts_with('?') {
+        return format!("/{path}");
+      }
+
+      if path.starts_with('/') {
+        return path.to_string();
+      }
+    }
+  }
+
+  format!(
+    "/{}/{}",
+    if configuration.is_raw() {
+      "raw"
+    } else if configuration.is_no_css() {
+      "nocss"
+    } else if configuration.is_json() {
+      "json"
+    } else if configuration.is_markdown() {
+      "md"
+    } else if configuration.is_text() {
+      "txt"
+    } else if configuration.is_terminal() {
+      "term"
+    } else {
+      "proxy"
+    },
+    url.as_str().trim_start_matches("gemini://")
+  )
+}
+
 pub fn matches_pattern(pattern: &str, path: &str) -> bool {
   if !pattern.contains('*') {
     return path == pattern;